@@ -580,6 +580,14 @@ define_bit_enum! {
     }
 }
 
+#[allow(non_snake_case)]
+impl GraphicsAllocatorUsage {
+    /// Represents the usual usage for render surfaces/framebuffers (composer, render and texture hardware usage)
+    pub const fn StandardFramebuffer() -> Self {
+        Self::from(Self::HardwareComposer().get() | Self::HardwareRender().get() | Self::HardwareTexture().get())
+    }
+}
+
 /// Represents connection APIs
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(i32)]
@@ -602,6 +610,8 @@ pub enum DisconnectMode {
 }
 
 /// Represents a queue buffer output layout
+/// 
+/// Note that this is the (older) layout the system's buffer queue uses, which doesn't have the newer Android fields (next frame number, whether a buffer was replaced), so dropped frames can't be detected through it
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(C)]
 pub struct QueueBufferOutput {
@@ -616,10 +626,20 @@ pub struct QueueBufferOutput {
 }
 
 impl QueueBufferOutput {
+    /// Represents the maximum width/height a sane [`QueueBufferOutput`] can report (way above any display/layer size the system supports)
+    pub const MAX_DIMENSION: u32 = 8192;
+    /// Represents the maximum pending buffer count a sane [`QueueBufferOutput`] can report (the buffer queue slot count)
+    pub const MAX_PENDING_BUFFER_COUNT: u32 = 64;
+
     /// Creates a new, empty [`QueueBufferOutput`]
     pub const fn new() -> Self {
         Self { width: 0, height: 0, transform_hint: 0, pending_buffer_count: 0 }
     }
+
+    /// Gets whether the dimensions are non-zero and the dimensions and the pending buffer count are within the sanity limits ([`QueueBufferOutput::MAX_DIMENSION`] and [`QueueBufferOutput::MAX_PENDING_BUFFER_COUNT`])
+    pub const fn is_sane(&self) -> bool {
+        (self.width > 0) && (self.width <= Self::MAX_DIMENSION) && (self.height > 0) && (self.height <= Self::MAX_DIMENSION) && (self.pending_buffer_count <= Self::MAX_PENDING_BUFFER_COUNT)
+    }
 }
 
 /// Represents a plane layout
@@ -731,6 +751,77 @@ impl GraphicBuffer {
     pub const MAGIC: u32 = 0xDAFFCAFF;
 }
 
+/// Represents an owned nvmap handle, which gets freed when dropped
+pub struct NvMapHandle {
+    nvdrv_srv: mem::Shared<dyn nv::INvDrvServices>,
+    nvmap_fd: nv::Fd,
+    handle: u32
+}
+
+impl NvMapHandle {
+    /// Creates a new [`NvMapHandle`], taking ownership of the given handle
+    /// 
+    /// # Arguments
+    /// 
+    /// * `nvdrv_srv`: The [`INvDrvServices`][`nv::INvDrvServices`] object the handle was created with
+    /// * `nvmap_fd`: The nvmap fd the handle was created with
+    /// * `handle`: The nvmap handle
+    #[inline]
+    pub fn new(nvdrv_srv: mem::Shared<dyn nv::INvDrvServices>, nvmap_fd: nv::Fd, handle: u32) -> Self {
+        Self { nvdrv_srv, nvmap_fd, handle }
+    }
+
+    /// Gets the raw nvmap handle
+    #[inline]
+    pub fn get_handle(&self) -> u32 {
+        self.handle
+    }
+}
+
+impl Drop for NvMapHandle {
+    /// Frees the nvmap handle
+    fn drop(&mut self) {
+        let mut ioctl_free: ioctl::NvMapFree = Default::default();
+        ioctl_free.handle = self.handle;
+        if let Ok(err) = self.nvdrv_srv.get().ioctl(self.nvmap_fd, nv::IoctlId::NvMapFree, sf::Buffer::from_other_var(&ioctl_free), sf::Buffer::from_other_var(&ioctl_free)) {
+            let _ = convert_nv_error_code(err);
+        }
+    }
+}
+
+/// Represents a [`GraphicBuffer`] tied to the nvmap allocation it refers to
+/// 
+/// The allocation is kept alive as long as any [`OwnedGraphicBuffer`] referring to it exists (several buffers might share the same allocation at different offsets), which prevents the allocation from being freed while the consumer might still read it
+pub struct OwnedGraphicBuffer {
+    buffer: GraphicBuffer,
+    nvmap_handle: mem::Shared<NvMapHandle>
+}
+
+impl OwnedGraphicBuffer {
+    /// Creates a new [`OwnedGraphicBuffer`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `buffer`: The [`GraphicBuffer`]
+    /// * `nvmap_handle`: The nvmap allocation the buffer refers to
+    #[inline]
+    pub fn new(buffer: GraphicBuffer, nvmap_handle: mem::Shared<NvMapHandle>) -> Self {
+        Self { buffer, nvmap_handle }
+    }
+
+    /// Gets the (raw) [`GraphicBuffer`]
+    #[inline]
+    pub fn get_buffer(&self) -> GraphicBuffer {
+        self.buffer
+    }
+
+    /// Gets the nvmap allocation the buffer refers to
+    #[inline]
+    pub fn get_nvmap_handle(&self) -> mem::Shared<NvMapHandle> {
+        self.nvmap_handle.clone()
+    }
+}
+
 /// Represents a fence layout
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(C)]
@@ -747,27 +838,161 @@ pub struct MultiFence {
     fences: [Fence; 4]
 }
 
+impl Fence {
+    /// Represents the ID of invalid fences
+    pub const INVALID_ID: u32 = u32::MAX;
+
+    /// Creates a new [`Fence`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `id`: The syncpoint ID
+    /// * `value`: The syncpoint value the fence gets signaled at
+    #[inline]
+    pub const fn new(id: u32, value: u32) -> Self {
+        Self { id, value }
+    }
+
+    /// Gets the syncpoint ID
+    #[inline]
+    pub const fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    /// Gets the syncpoint value
+    #[inline]
+    pub const fn get_value(&self) -> u32 {
+        self.value
+    }
+
+    /// Gets whether this [`Fence`] is valid (thus whether its ID isn't [`INVALID_ID`][`Fence::INVALID_ID`] or zero)
+    #[inline]
+    pub const fn is_valid(&self) -> bool {
+        (self.id != Self::INVALID_ID) && (self.id != 0)
+    }
+}
+
+impl MultiFence {
+    /// Represents the maximum amount of fences a [`MultiFence`] can hold
+    pub const MAX_FENCE_COUNT: usize = 4;
+
+    /// Creates a [`MultiFence`] holding a single [`Fence`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `fence`: The fence
+    pub const fn from_fence(fence: Fence) -> Self {
+        let mut multi_fence = Self { fence_count: 1, fences: [Fence::new(Fence::INVALID_ID, 0); Self::MAX_FENCE_COUNT] };
+        multi_fence.fences[0] = fence;
+        multi_fence
+    }
+
+    fn valid_fences(&self) -> impl Iterator<Item = &Fence> {
+        let fence_count = (self.fence_count as usize).min(Self::MAX_FENCE_COUNT);
+        self.fences[..fence_count].iter().filter(|fence| fence.is_valid())
+    }
+
+    /// Gets the amount of valid fences (see [`Fence::is_valid`]) in this [`MultiFence`]
+    pub fn valid_count(&self) -> usize {
+        self.valid_fences().count()
+    }
+
+    /// Merges this [`MultiFence`] with another one, returning the merged [`MultiFence`]
+    /// 
+    /// Invalid fences (see [`Fence::is_valid`]) of both [`MultiFence`]s are skipped
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other`: The other [`MultiFence`]
+    pub fn merge(&self, other: &MultiFence) -> Result<MultiFence> {
+        result_return_if!(self.valid_count() + other.valid_count() > Self::MAX_FENCE_COUNT, rc::ResultTooManyFences);
+
+        let mut merged: MultiFence = Default::default();
+        for fence in self.valid_fences().chain(other.valid_fences()) {
+            merged.fences[merged.fence_count as usize] = *fence;
+            merged.fence_count += 1;
+        }
+        Ok(merged)
+    }
+}
+
 /// Represenrs a rectangle layout
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
 #[repr(C)]
 pub struct Rect {
-    left: i32,
-    top: i32,
-    right: i32,
-    bottom: i32
+    /// The left coordinate
+    pub left: i32,
+    /// The top coordinate
+    pub top: i32,
+    /// The right coordinate
+    pub right: i32,
+    /// The bottom coordinate
+    pub bottom: i32
 }
 
-/// Represents a transform type
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
-#[repr(u32)]
-pub enum Transform {
-    #[default]
-    Invalid = 0,
-    FlipH = 1,
-    FlipV = 2,
-    Rotate90 = 4,
-    Rotate180 = 3,
-    Rotate270 = 7
+impl Rect {
+    /// Creates a new [`Rect`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `left`: The left coordinate
+    /// * `top`: The top coordinate
+    /// * `right`: The right coordinate
+    /// * `bottom`: The bottom coordinate
+    pub const fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self { left, top, right, bottom }
+    }
+
+    /// Gets the width (which is negative for non-ordered coordinates)
+    pub const fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    /// Gets the height (which is negative for non-ordered coordinates)
+    pub const fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    /// Gets whether the rectangle has no area
+    /// 
+    /// Note that an empty crop rectangle means no cropping at all
+    pub const fn is_empty(&self) -> bool {
+        (self.width() <= 0) || (self.height() <= 0)
+    }
+
+    /// Gets whether the coordinates are ordered (`right >= left` and `bottom >= top`), which is required for crop rectangles
+    pub const fn is_valid_crop(&self) -> bool {
+        (self.right >= self.left) && (self.bottom >= self.top)
+    }
+}
+
+define_bit_enum! {
+    /// Represents transform flags
+    Transform (u32) {
+        Invalid = 0,
+        FlipH = bit!(0),
+        FlipV = bit!(1),
+        Rotate90 = bit!(2),
+        Rotate180 = bit!(0) | bit!(1),
+        Rotate270 = bit!(0) | bit!(1) | bit!(2)
+    }
+}
+
+impl Transform {
+    /// Gets the [`Transform`] corresponding to a (clockwise) rotation angle, if it's a multiple of 90 degrees
+    /// 
+    /// # Arguments
+    /// 
+    /// * `degrees`: The rotation angle, in degrees
+    pub const fn from_rotation(degrees: u32) -> Option<Self> {
+        match degrees % 360 {
+            0 => Some(Self::Invalid()),
+            90 => Some(Self::Rotate90()),
+            180 => Some(Self::Rotate180()),
+            270 => Some(Self::Rotate270()),
+            _ => None
+        }
+    }
 }
 
 /// Represents a queue buffer input layout
@@ -775,15 +1000,24 @@ pub enum Transform {
 #[repr(C)]
 #[repr(packed)]
 pub struct QueueBufferInput {
-    timestamp: i64,
-    is_auto_timestamp: i32,
-    crop: Rect,
-    scaling_mode: i32,
-    transform: Transform,
-    sticky_transform: u32,
-    unk: u32,
-    swap_interval: u32,
-    fences: MultiFence
+    /// The frame timestamp
+    pub timestamp: i64,
+    /// Whether the timestamp is automatically generated (serialized as a 32-bit value)
+    pub is_auto_timestamp: i32,
+    /// The crop rectangle
+    pub crop: Rect,
+    /// The scaling mode
+    pub scaling_mode: i32,
+    /// The transform
+    pub transform: Transform,
+    /// The sticky transform
+    pub sticky_transform: Transform,
+    /// Unknown value
+    pub unk: u32,
+    /// The swap interval
+    pub swap_interval: u32,
+    /// The buffer fences
+    pub fences: MultiFence
 }
 
 /// Represents the base-2 log value of the block height
@@ -896,7 +1130,7 @@ pub struct Context {
     vi_service: mem::Shared<dyn sf::IObject>,
     nvdrv_service: mem::Shared<dyn INvDrvServices>,
     application_display_service: mem::Shared<dyn IApplicationDisplayService>,
-    hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>,
+    binder_factory: binder::BinderFactory,
     transfer_mem: alloc::Buffer<u8>,
     transfer_mem_handle: svc::Handle,
     nvhost_fd: u32,
@@ -976,7 +1210,7 @@ impl Context {
         convert_nv_error_code(nvhostctrl_err)?;
         
         let hos_binder_drv = application_display_srv.get().get_relay_service()?;
-        Ok(Self { vi_service: vi_srv, nvdrv_service: nvdrv_srv, application_display_service: application_display_srv, hos_binder_driver: hos_binder_drv, transfer_mem, transfer_mem_handle, nvhost_fd, nvmap_fd, nvhostctrl_fd })
+        Ok(Self { vi_service: vi_srv, nvdrv_service: nvdrv_srv, application_display_service: application_display_srv, binder_factory: binder::BinderFactory::new(hos_binder_drv), transfer_mem, transfer_mem_handle, nvhost_fd, nvmap_fd, nvhostctrl_fd })
     }
 
     /// Gets the underlying NV [`INvDrvServices`] service object
@@ -991,7 +1225,12 @@ impl Context {
 
     /// Gets the underlying [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
     pub fn get_hos_binder_driver(&self) -> mem::Shared<dyn dispdrv::IHOSBinderDriver> {
-        self.hos_binder_driver.clone()
+        self.binder_factory.get_hos_binder_driver()
+    }
+
+    /// Gets the underlying [`BinderFactory`][`binder::BinderFactory`], which shares this context's [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
+    pub fn get_binder_factory(&self) -> &binder::BinderFactory {
+        &self.binder_factory
     }
 
     fn stray_layer_destroy(layer_id: vi::LayerId, application_display_service: mem::Shared<dyn IApplicationDisplayService>) -> Result<()> {
@@ -1005,10 +1244,10 @@ impl Context {
 
     fn create_surface_impl(&mut self, buffer_count: u32, display_id: vi::DisplayId, layer_id: vi::LayerId, width: u32, height: u32, color_fmt: ColorFormat, pixel_fmt: PixelFormat, layout: Layout, layer_destroy_fn: surface::LayerDestroyFn, native_window: parcel::ParcelPayload) -> Result<surface::Surface> {
         let mut parcel = parcel::Parcel::new();
-        parcel.load_from(native_window);
+        parcel.load_from(native_window)?;
         
         let data: parcel::ParcelData = parcel.read()?;
-        surface::Surface::new(data.handle, self.nvdrv_service.clone(), self.application_display_service.clone(), self.nvhost_fd, self.nvmap_fd, self.nvhostctrl_fd, self.hos_binder_driver.clone(), buffer_count, display_id, layer_id, width, height, color_fmt, pixel_fmt, layout, layer_destroy_fn)
+        surface::Surface::new(data.handle, self.nvdrv_service.clone(), self.application_display_service.clone(), self.nvhost_fd, self.nvmap_fd, self.nvhostctrl_fd, self.binder_factory.get_hos_binder_driver(), buffer_count, display_id, layer_id, width, height, color_fmt, pixel_fmt, layout, layer_destroy_fn)
     }
 
     /// Creates a [`Surface`][`surface::Surface`] based on a stray layer
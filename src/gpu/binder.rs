@@ -1,5 +1,7 @@
 //! Binder support and utils
 
+extern crate alloc as core_alloc;
+use core_alloc::vec::Vec;
 use crate::result::*;
 use crate::ipc::sf;
 use crate::gpu::parcel;
@@ -35,8 +37,43 @@ pub enum ErrorCode {
     BadType = -2147483647,
 }
 
+impl ErrorCode {
+    /// Gets the [`ErrorCode`] corresponding to a raw value (as read from a parcel), if it's a known one
+    /// 
+    /// # Arguments
+    /// 
+    /// * `raw`: The raw value
+    pub const fn from_raw(raw: i32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Success),
+            -1 => Some(Self::PermissionDenied),
+            -2 => Some(Self::NameNotFound),
+            -11 => Some(Self::WouldBlock),
+            -12 => Some(Self::NoMemory),
+            -17 => Some(Self::AlreadyExists),
+            -19 => Some(Self::NoInit),
+            -22 => Some(Self::BadValue),
+            -32 => Some(Self::DeadObject),
+            -38 => Some(Self::InvalidOperation),
+            -61 => Some(Self::NotEnoughData),
+            -74 => Some(Self::UnknownTransaction),
+            -75 => Some(Self::BadIndex),
+            -110 => Some(Self::TimeOut),
+            -2147483641 => Some(Self::FdsNotAllowed),
+            -2147483646 => Some(Self::FailedTransaction),
+            -2147483647 => Some(Self::BadType),
+            _ => None
+        }
+    }
+}
+
 /// Converts [`ErrorCode`]s to result values
-#[allow(unreachable_patterns)]
+/// 
+/// Note that this match is intentionally exhaustive, so that adding a new [`ErrorCode`] without its corresponding result is a compile error
+/// 
+/// # Arguments
+/// 
+/// * `err`: The [`ErrorCode`]
 pub fn convert_nv_error_code(err: ErrorCode) -> Result<()> {
     match err {
         ErrorCode::Success => Ok(()),
@@ -55,15 +92,83 @@ pub fn convert_nv_error_code(err: ErrorCode) -> Result<()> {
         ErrorCode::TimeOut => rc::ResultErrorCodeTimeOut::make_err(),
         ErrorCode::FdsNotAllowed => rc::ResultErrorCodeFdsNotAllowed::make_err(),
         ErrorCode::FailedTransaction => rc::ResultErrorCodeFailedTransaction::make_err(),
-        ErrorCode::BadType => rc::ResultErrorCodeBadType::make_err(),
-        _ => rc::ResultErrorCodeInvalid::make_err(),
+        ErrorCode::BadType => rc::ResultErrorCodeBadType::make_err()
+    }
+}
+
+/// Converts raw error code values (as read from parcels) to result values
+/// 
+/// Values not corresponding to any [`ErrorCode`] are converted to [`ResultErrorCodeInvalid`][`rc::ResultErrorCodeInvalid`]
+/// 
+/// # Arguments
+/// 
+/// * `raw_err`: The raw error code value
+pub fn convert_raw_nv_error_code(raw_err: i32) -> Result<()> {
+    match ErrorCode::from_raw(raw_err) {
+        Some(err) => convert_nv_error_code(err),
+        None => rc::ResultErrorCodeInvalid::make_err()
+    }
+}
+
+/// Represents a factory of [`Binder`]s, all sharing the same [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] session
+/// 
+/// Every [`Binder`] created through it holds a reference to the driver, so the driver session is only closed once the factory and all the [`Binder`]s it created are dropped
+pub struct BinderFactory {
+    hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>
+}
+
+impl BinderFactory {
+    /// Creates a new [`BinderFactory`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `hos_binder_driver`: [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object to share
+    #[inline]
+    pub const fn new(hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>) -> Self {
+        Self { hos_binder_driver }
+    }
+
+    /// Creates a new [`Binder`] for the given handle, using the shared [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
+    /// 
+    /// # Arguments
+    /// 
+    /// * `handle`: Binder handle to use
+    pub fn create_binder(&self, handle: dispdrv::BinderHandle) -> Result<Binder> {
+        Binder::new(handle, self.hos_binder_driver.clone())
+    }
+
+    /// Gets the shared [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
+    #[inline]
+    pub fn get_hos_binder_driver(&self) -> mem::Shared<dyn dispdrv::IHOSBinderDriver> {
+        self.hos_binder_driver.clone()
     }
 }
 
+/// Represents the amount of buffer slots a buffer queue has
+pub const BUFFER_SLOT_COUNT: usize = 64;
+
+/// Represents the state of a buffer slot, as tracked by a [`Binder`]
+/// 
+/// Note that the consumer side isn't observable from here, thus [`SlotState::Queued`] slots stay that way until they're dequeued again
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum SlotState {
+    /// The slot wasn't dequeued (or was cancelled)
+    #[default]
+    Free,
+    /// The slot is dequeued, thus owned by the producer
+    Dequeued,
+    /// The slot was queued, thus handed to the consumer
+    Queued
+}
+
 /// Represents a binder object, wrapping transaction functionality
 pub struct Binder {
     handle: dispdrv::BinderHandle,
     hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>,
+    connected_api: Option<ConnectionApi>,
+    owned_buffers: Vec<(i32, OwnedGraphicBuffer)>,
+    slot_states: [SlotState; BUFFER_SLOT_COUNT],
+    validate_response_shape: bool
 }
 
 impl Binder {
@@ -75,7 +180,7 @@ impl Binder {
     /// * `hos_binder_driver`: [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
     #[inline]
     pub const fn new(handle: dispdrv::BinderHandle, hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>) -> Result<Self> {
-        Ok(Self { handle, hos_binder_driver })
+        Ok(Self { handle, hos_binder_driver, connected_api: None, owned_buffers: Vec::new(), slot_states: [SlotState::Free; BUFFER_SLOT_COUNT], validate_response_shape: false })
     }
 
     fn transact_parcel_begin(&self, parcel: &mut parcel::Parcel) -> Result<()> {
@@ -83,25 +188,88 @@ impl Binder {
     }
 
     fn transact_parcel_check_err(&mut self, parcel: &mut parcel::Parcel) -> Result<()> {
-        let err: ErrorCode = parcel.read()?;
-        convert_nv_error_code(err)?;
+        // The error code is always the last value of a response, thus (once every expected field was read) exactly it must be left
+        // Otherwise the response doesn't have the shape of the expected transaction's one, and whatever is read as the error code is meaningless
+        if self.validate_response_shape {
+            result_return_unless!(parcel.get_remaining_read_size() == core::mem::size_of::<i32>(), parcel::rc::ResultBadParcel);
+        }
+
+        // Read the raw value, since reading an out-of-enum value directly as an ErrorCode would be UB
+        let raw_err: i32 = parcel.read()?;
+        convert_raw_nv_error_code(raw_err)?;
         Ok(())
     }
 
-    fn transact_parcel_impl(&mut self, transaction_id: dispdrv::ParcelTransactionId, payload: parcel::ParcelPayload) -> Result<parcel::Parcel> {
-        let response_payload = parcel::ParcelPayload::new();
-        self.hos_binder_driver.get().transact_parcel(self.handle, transaction_id, 0, sf::Buffer::from_other_var(&payload), sf::Buffer::from_other_var(&response_payload))?;
+    fn transact_parcel_impl<const R: usize>(&mut self, transaction_id: dispdrv::ParcelTransactionId, payload: parcel::ParcelPayload) -> Result<parcel::Parcel<R>> {
+        // Responses are received into a fixed-size payload (R bytes), a response which didn't fit is rejected when loading it below instead of being read truncated
+        let response_payload = parcel::ParcelPayload::<R>::new();
+        // Driver failures (like a dead driver) are the result of the command itself, thus distinct from the producer errors returned inside the response parcel (see transact_parcel_check_err)
+        // The auto-select variant (3.0.0+) lets the driver pick pointer buffers for small parcels instead of always mapping them
+        if crate::version::get_version() >= crate::version::Version::new(3,0,0) {
+            self.hos_binder_driver.get().transact_parcel_auto(self.handle, transaction_id, 0, sf::Buffer::from_other_var(&payload), sf::Buffer::from_other_var(&response_payload))?;
+        }
+        else {
+            self.hos_binder_driver.get().transact_parcel(self.handle, transaction_id, 0, sf::Buffer::from_other_var(&payload), sf::Buffer::from_other_var(&response_payload))?;
+        }
         
         let mut parcel = parcel::Parcel::new();
-        parcel.load_from(response_payload);
+        parcel.load_from(response_payload)?;
         Ok(parcel)
     }
 
     fn transact_parcel(&mut self, transaction_id: dispdrv::ParcelTransactionId, parcel: &mut parcel::Parcel) -> Result<parcel::Parcel> {
+        self.transact(transaction_id, parcel)
+    }
+
+    /// Performs a raw parcel transaction, for transactions not wrapped by [`Binder`] or whose response might not fit in the default payload size
+    /// 
+    /// The response is received into a payload of `R` bytes ([`PAYLOAD_SIZE`][`parcel::PAYLOAD_SIZE`] being the default one), a response which doesn't fit in it fails with [`ResultBadParcel`][`parcel::rc::ResultBadParcel`] instead of being read truncated
+    /// 
+    /// For instance, `binder.transact::<0x1000>(dispdrv::ParcelTransactionId::GetBufferHistory, &mut parcel)` receives a response of up to 0x1000 bytes
+    /// 
+    /// The request parcel must start with the interface token (see [`INTERFACE_TOKEN`]), and the response ends with the transaction's error code (see [`convert_raw_nv_error_code`])
+    /// 
+    /// # Arguments
+    /// 
+    /// * `transaction_id`: The transaction to perform
+    /// * `parcel`: The request parcel
+    pub fn transact<const R: usize>(&mut self, transaction_id: dispdrv::ParcelTransactionId, parcel: &mut parcel::Parcel) -> Result<parcel::Parcel<R>> {
         let (payload, _payload_size) = parcel.end_write()?;
         self.transact_parcel_impl(transaction_id, payload)
     }
 
+    /// Sets whether response parcels are validated to have the shape of the expected transaction's response (disabled by default)
+    /// 
+    /// When enabled, responses with more or less data than the fields read from them plus the error code are rejected with [`ResultBadParcel`][`parcel::rc::ResultBadParcel`], instead of reading (and possibly accepting) a bogus error code from a misrouted or corrupted response
+    /// 
+    /// # Arguments
+    /// 
+    /// * `enabled`: Whether to validate response shapes
+    #[inline]
+    pub fn set_validate_response_shape(&mut self, enabled: bool) {
+        self.validate_response_shape = enabled;
+    }
+
+    /// Gets the [`ConnectionApi`] this [`Binder`] is currently connected with, if any
+    #[inline]
+    pub fn get_connected_api(&self) -> Option<ConnectionApi> {
+        self.connected_api
+    }
+
+    /// Gets the state of every buffer slot (indexed by slot), as tracked through this [`Binder`]'s dequeue/queue/cancel calls
+    /// 
+    /// This is meant for debugging stuck swapchains, like a slot which is never queued back after being dequeued
+    #[inline]
+    pub fn slot_states(&self) -> &[SlotState] {
+        &self.slot_states
+    }
+
+    fn set_slot_state(&mut self, slot: i32, state: SlotState) {
+        if let Some(slot_state) = self.slot_states.get_mut(slot as usize) {
+            *slot_state = state;
+        }
+    }
+
     /// Gets this [`Binder`]'s handle
     #[inline]
     pub fn get_handle(&self) -> dispdrv::BinderHandle {
@@ -127,6 +295,8 @@ impl Binder {
 
     /// Performs a connection
     /// 
+    /// If the resulting [`QueueBufferOutput`] isn't sane (see [`QueueBufferOutput::is_sane`]), [`ResultErrorCodeBadValue`][`rc::ResultErrorCodeBadValue`] is returned, the connection being undone before returning the error (thus the caller must not disconnect it again)
+    /// 
     /// # Arguments
     /// 
     /// * `api`: The connection API to use
@@ -144,16 +314,29 @@ impl Binder {
         let qbo: QueueBufferOutput = response_parcel.read()?;
 
         self.transact_parcel_check_err(&mut response_parcel)?;
+        self.connected_api = Some(api);
+        // A bogus output means a broken connection (like a misconfigured display setup), so fail here instead of dequeuing against it later
+        // The connection was already made though, thus it's undone first (callers can't be expected to disconnect after a failed connection)
+        if !qbo.is_sane() {
+            let _ = self.disconnect(api, DisconnectMode::Api);
+            return rc::ResultErrorCodeBadValue::make_err();
+        }
         Ok(qbo)
     }
 
     /// Performs a disconnection
     /// 
+    /// When disconnecting a specific API ([`DisconnectMode::Api`]), it must match the one last connected through this [`Binder`], otherwise [`ResultErrorCodeBadValue`][`rc::ResultErrorCodeBadValue`] is returned without performing the transaction
+    /// 
     /// # Arguments
     /// 
     /// * `api`: The connection API
     /// * `mode`: The disconnection mode
     pub fn disconnect(&mut self, api: ConnectionApi, mode: DisconnectMode) -> Result<()> {
+        if mode == DisconnectMode::Api {
+            result_return_unless!(self.connected_api == Some(api), rc::ResultErrorCodeBadValue);
+        }
+
         let mut parcel = parcel::Parcel::new();
         self.transact_parcel_begin(&mut parcel)?;
 
@@ -163,6 +346,11 @@ impl Binder {
         let mut response_parcel = self.transact_parcel(dispdrv::ParcelTransactionId::Disconnect, &mut parcel)?;
 
         self.transact_parcel_check_err(&mut response_parcel)?;
+        self.connected_api = None;
+        self.slot_states = [SlotState::Free; BUFFER_SLOT_COUNT];
+
+        // The consumer won't access the buffers anymore, thus their allocations can finally be released
+        self.owned_buffers.clear();
         Ok(())
     }
 
@@ -187,6 +375,20 @@ impl Binder {
         Ok(())
     }
     
+    /// Sets a preallocated buffer, keeping its nvmap allocation alive until this [`Binder`] gets disconnected (or another buffer is set at the same slot)
+    /// 
+    /// # Arguments
+    /// 
+    /// * `slot`: The buffer slot
+    /// * `buf`: The owned buffer
+    pub fn set_preallocated_owned_buffer(&mut self, slot: i32, buf: OwnedGraphicBuffer) -> Result<()> {
+        self.set_preallocated_buffer(slot, buf.get_buffer())?;
+
+        self.owned_buffers.retain(|(owned_slot, _)| *owned_slot != slot);
+        self.owned_buffers.push((slot, buf));
+        Ok(())
+    }
+    
     /// Requests a buffer at a given slot
     /// 
     /// This also returns whether the buffer is non-null
@@ -242,16 +444,23 @@ impl Binder {
         }
 
         self.transact_parcel_check_err(&mut response_parcel)?;
+        self.set_slot_state(slot, SlotState::Dequeued);
         Ok((slot, has_fences, fences))
     }
 
     /// Queues a buffer
     /// 
+    /// The input layout (timestamp, crop, scaling mode, transforms, swap interval and fences) is written unmodified, following the layout expected by the buffer queue
+    /// 
+    /// If the crop rectangle isn't valid (see [`Rect::is_valid_crop`]), [`ResultErrorCodeBadValue`][`rc::ResultErrorCodeBadValue`] is returned without performing the transaction
+    /// 
     /// # Arguments
     /// 
     /// * `slot`: The slot
     /// * `qbi`: The input layout
     pub fn queue_buffer(&mut self, slot: i32, qbi: QueueBufferInput) -> Result<QueueBufferOutput> {
+        result_return_unless!(qbi.crop.is_valid_crop(), rc::ResultErrorCodeBadValue);
+
         let mut parcel = parcel::Parcel::new();
         self.transact_parcel_begin(&mut parcel)?;
 
@@ -263,11 +472,34 @@ impl Binder {
         let qbo = response_parcel.read()?;
 
         self.transact_parcel_check_err(&mut response_parcel)?;
+        self.set_slot_state(slot, SlotState::Queued);
         Ok(qbo)
     }
 
+    /// Cancels a dequeued buffer, returning it to the queue without presenting it
+    /// 
+    /// # Arguments
+    /// 
+    /// * `slot`: The slot
+    /// * `fences`: The fences to wait for before the buffer can be reused
+    pub fn cancel_buffer(&mut self, slot: i32, fences: MultiFence) -> Result<()> {
+        let mut parcel = parcel::Parcel::new();
+        self.transact_parcel_begin(&mut parcel)?;
+
+        parcel.write(slot)?;
+        parcel.write_sized(fences)?;
+
+        let mut response_parcel = self.transact_parcel(dispdrv::ParcelTransactionId::CancelBuffer, &mut parcel)?;
+
+        self.transact_parcel_check_err(&mut response_parcel)?;
+        self.set_slot_state(slot, SlotState::Free);
+        Ok(())
+    }
+
     /// Gets a native handle of the underlying [`IHOSBinderDriver`][`dispdrv::IHOSBinderDriver`] object
     /// 
+    /// Note that only the buffer event is exposed this way, display vsync events must be obtained via [`get_display_vsync_event`][`crate::ipc::sf::vi::IApplicationDisplayService::get_display_vsync_event`]
+    /// 
     /// # Arguments
     /// 
     /// * `handle_type`: The [`NativeHandleType`][`dispdrv::NativeHandleType`] value
@@ -108,6 +108,34 @@ impl Ioctl for NvMapAlloc {
     }
 }
 
+/// Represents the `Free` command for [`NvMap`][`IoctlFd::NvMap`] fd
+/// 
+/// See <https://switchbrew.org/wiki/NV_services#NVMAP_IOC_FREE>
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[repr(C)]
+pub struct NvMapFree {
+    /// The input handle
+    pub handle: u32,
+    /// Padding
+    pub pad: u32,
+    /// The output address
+    pub address: usize,
+    /// The output size
+    pub size: u32,
+    /// The output flags
+    pub flags: u32
+}
+
+impl Ioctl for NvMapFree {
+    fn get_id() -> nv::IoctlId {
+        nv::IoctlId::NvMapFree
+    }
+
+    fn get_fd() -> IoctlFd {
+        IoctlFd::NvMap
+    }
+}
+
 /// Represents the `GetId` command for [`NvMap`][`IoctlFd::NvMap`] fd
 /// 
 /// See <https://switchbrew.org/wiki/NV_services#NVMAP_IOC_GET_ID>
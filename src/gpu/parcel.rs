@@ -30,25 +30,30 @@ impl ParcelHeader {
     }
 }
 
-const PAYLOAD_SIZE: usize = 0x200;
+/// Represents the default maximum size of the data contained in a [`ParcelPayload`]
+/// 
+/// This is large enough for every transaction currently implemented (request and response)
+/// 
+/// Transactions returning larger parcels (like `GetBufferHistory` with many entries) need a larger payload, which can be requested through the `S` parameter of [`ParcelPayload`]/[`Parcel`] (see [`Binder::transact`][`crate::gpu::binder::Binder::transact`])
+pub const PAYLOAD_SIZE: usize = 0x200;
 
 /// Represents a parcel payload layout
 /// 
-/// Note that a parcel payload length is variable, but we use a maximum size for this type
+/// Note that a parcel payload length is variable, but we use a maximum size for this type (`S`, [`PAYLOAD_SIZE`] by default)
 #[derive(Copy, Clone)]
 #[repr(C)]
-pub struct ParcelPayload {
+pub struct ParcelPayload<const S: usize = PAYLOAD_SIZE> {
     /// The header
     pub header: ParcelHeader,
     /// The actual payload
-    pub payload: [u8; PAYLOAD_SIZE]
+    pub payload: [u8; S]
 }
 
-impl ParcelPayload {
+impl<const S: usize> ParcelPayload<S> {
     /// Creates a new, empty [`ParcelPayload`]
     #[inline]
     pub const fn new() -> Self {
-        Self { header: ParcelHeader::new(), payload: [0; PAYLOAD_SIZE] }
+        Self { header: ParcelHeader::new(), payload: [0; S] }
     }
 }
 
@@ -70,13 +75,15 @@ pub struct ParcelData {
 }
 
 /// Represents a wrapper for simple parcel reading/writing
-pub struct Parcel {
-    payload: ParcelPayload,
+/// 
+/// The payload is stored inline (`S`, [`PAYLOAD_SIZE`] by default), thus parcels never allocate heap memory: writing past the payload fails with [`ResultNotEnoughWriteSpace`][`rc::ResultNotEnoughWriteSpace`] instead
+pub struct Parcel<const S: usize = PAYLOAD_SIZE> {
+    payload: ParcelPayload<S>,
     read_offset: usize,
     write_offset: usize
 }
 
-impl Parcel {
+impl<const S: usize> Parcel<S> {
     /// Creates a new [`Parcel`]
     #[inline]
     pub const fn new() -> Self {
@@ -90,7 +97,7 @@ impl Parcel {
     /// * `out_data`: Out data buffer
     /// * `data_size`: Out data size
     pub fn read_raw_unaligned(&mut self, out_data: *mut u8, data_size: usize) -> Result<()> {
-        result_return_if!((self.read_offset + data_size) > PAYLOAD_SIZE, rc::ResultNotEnoughReadSpace);
+        result_return_if!((self.read_offset + data_size) > S, rc::ResultNotEnoughReadSpace);
 
         unsafe {
             ptr::copy((&mut self.payload.payload as *mut _ as *mut u8).add(self.read_offset), out_data, data_size);
@@ -112,6 +119,54 @@ impl Parcel {
         self.read_raw_unaligned(out_data, mem::align_up(data_size, 4))
     }
 
+    /// Gets the current read position (offset) within the payload
+    #[inline]
+    pub fn get_read_position(&self) -> usize {
+        self.read_offset
+    }
+
+    /// Gets the remaining data size to be read
+    #[inline]
+    pub fn get_remaining_read_size(&self) -> usize {
+        self.write_offset.saturating_sub(self.read_offset)
+    }
+
+    /// Sets the current read position (offset) within the payload, for instance to rewind and re-read data
+    /// 
+    /// # Arguments
+    /// 
+    /// * `position`: The new read position, which can't go past the written/loaded data
+    pub fn set_read_position(&mut self, position: usize) -> Result<()> {
+        result_return_if!(position > self.write_offset, rc::ResultNotEnoughReadSpace);
+
+        self.read_offset = position;
+        Ok(())
+    }
+
+    /// Skips raw, unaligned data
+    /// 
+    /// # Arguments
+    /// 
+    /// * `data_size`: The size to skip, which can't exceed the remaining data size
+    pub fn skip_unaligned(&mut self, data_size: usize) -> Result<()> {
+        result_return_if!(data_size > self.get_remaining_read_size(), rc::ResultNotEnoughReadSpace);
+
+        self.read_offset += data_size;
+        Ok(())
+    }
+
+    /// Skips raw (aligned) data
+    /// 
+    /// This essentially aligns up the skipped size to a 4-byte align, like [`Parcel::read_raw`] does
+    /// 
+    /// # Arguments
+    /// 
+    /// * `data_size`: The size to skip
+    #[inline]
+    pub fn skip(&mut self, data_size: usize) -> Result<()> {
+        self.skip_unaligned(mem::align_up(data_size, 4))
+    }
+
     /// Writes raw, unaligned data
     /// 
     /// # Arguments
@@ -119,7 +174,7 @@ impl Parcel {
     /// * `data`: In data buffer
     /// * `data_size`: In data size
     pub fn write_raw_unaligned(&mut self, data: *const u8, data_size: usize) -> Result<()> {
-        result_return_if!((self.write_offset + data_size) > PAYLOAD_SIZE, rc::ResultNotEnoughWriteSpace);
+        result_return_if!((self.write_offset + data_size) > S, rc::ResultNotEnoughWriteSpace);
 
         unsafe {
             ptr::copy(data, (&mut self.payload.payload as *mut _ as *mut u8).add(self.write_offset), data_size);
@@ -135,7 +190,7 @@ impl Parcel {
     /// * `data_size`: Out data size
     pub fn write_reserve_raw(&mut self, data_size: usize) -> Result<*mut u8> {
         let actual_size = mem::align_up(data_size, 4);
-        result_return_if!((self.write_offset + actual_size) > PAYLOAD_SIZE, rc::ResultNotEnoughWriteSpace);
+        result_return_if!((self.write_offset + actual_size) > S, rc::ResultNotEnoughWriteSpace);
 
         let buf = unsafe { (&mut self.payload.payload as *mut _ as *mut u8).add(self.write_offset) };
         self.write_offset += actual_size;
@@ -276,19 +331,29 @@ impl Parcel {
 
     /// Loads an external payload in this [`Parcel`]
     /// 
+    /// The sizes/offsets declared in the payload header are validated to fit within the payload, returning [`ResultBadParcel`][`rc::ResultBadParcel`] otherwise (for instance, a response which didn't fit in it)
+    /// 
     /// # Arguments
     /// 
     /// * `payload`: The payload
-    pub fn load_from(&mut self, payload: ParcelPayload) {
+    pub fn load_from(&mut self, payload: ParcelPayload<S>) -> Result<()> {
+        let header = payload.header;
+        let payload_end = header.payload_offset.checked_add(header.payload_size).ok_or(rc::ResultBadParcel::make())?;
+        let objects_end = header.objects_offset.checked_add(header.objects_size).ok_or(rc::ResultBadParcel::make())?;
+        result_return_unless!(header.payload_size as usize <= S, rc::ResultBadParcel);
+        result_return_unless!(payload_end as usize <= cmem::size_of::<ParcelPayload<S>>(), rc::ResultBadParcel);
+        result_return_unless!(objects_end as usize <= cmem::size_of::<ParcelPayload<S>>(), rc::ResultBadParcel);
+
         self.payload = payload;
         self.read_offset = 0;
-        self.write_offset = payload.header.payload_size as usize;
+        self.write_offset = header.payload_size as usize;
+        Ok(())
     }
 
     /// Finishes writing and produces the payload
     /// 
     /// Essentially populates the payload header and returns the current payload, along with its size
-    pub fn end_write(&mut self) -> Result<(ParcelPayload, usize)> {
+    pub fn end_write(&mut self) -> Result<(ParcelPayload<S>, usize)> {
         self.payload.header.payload_size = self.write_offset as u32;
         self.payload.header.payload_offset = cmem::size_of::<ParcelHeader>() as u32;
         let payload_len = self.payload.header.payload_offset + self.payload.header.payload_size;
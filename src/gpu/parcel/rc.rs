@@ -8,5 +8,6 @@ result_define_subgroup!(rc::RESULT_MODULE, RESULT_SUBMODULE => {
     NotEnoughReadSpace: 1,
     NotEnoughWriteSpace: 2,
     FdsNotSupported: 3,
-    ReadSizeMismatch: 4
+    ReadSizeMismatch: 4,
+    BadParcel: 5
 });
\ No newline at end of file
@@ -23,5 +23,6 @@ result_define_subgroup!(rc::RESULT_MODULE, RESULT_SUBMODULE => {
     NvErrorCodeCountMismatch: 16,
     NvErrorCodeSharedMemoryTooSmall: 17,
     NvErrorCodeFileOperationFailed: 18,
-    NvErrorCodeIoctlFailed: 19
+    NvErrorCodeIoctlFailed: 19,
+    TooManyFences: 20
 });
\ No newline at end of file
@@ -57,15 +57,48 @@ impl Surface {
     /// * ``
     pub fn new(binder_handle: i32, nvdrv_srv: mem::Shared<dyn nv::INvDrvServices>, application_display_service: mem::Shared<dyn vi::IApplicationDisplayService>, nvhost_fd: u32, nvmap_fd: u32, nvhostctrl_fd: u32, hos_binder_driver: mem::Shared<dyn dispdrv::IHOSBinderDriver>, buffer_count: u32, display_id: vi::DisplayId, layer_id: vi::LayerId, width: u32, height: u32, color_fmt: ColorFormat, pixel_fmt: PixelFormat, layout: Layout, layer_destroy_fn: LayerDestroyFn) -> Result<Self> {
         let mut binder = binder::Binder::new(binder_handle, hos_binder_driver)?;
-        binder.increase_refcounts()?;
-        let _ = binder.connect(ConnectionApi::Cpu, false)?;
-        let vsync_event_handle = application_display_service.get().get_display_vsync_event(display_id)?;
-        let buffer_event_handle = binder.get_native_handle(dispdrv::NativeHandleType::BufferEvent)?;
-        let mut surface = Self { binder, nvdrv_srv, application_display_service, width, height, buffer_data: alloc::Buffer::empty(), single_buffer_size: 0, buffer_count, slot_has_requested: [false; MAX_BUFFERS], graphic_buf: Default::default(), color_fmt, pixel_fmt, layout, display_id, layer_id, layer_destroy_fn, nvhost_fd, nvmap_fd, nvhostctrl_fd, vsync_event_handle: vsync_event_handle.handle, buffer_event_handle: buffer_event_handle.handle };
+        let (vsync_event_handle, buffer_event_handle) = match Self::connect_binder(&mut binder, &application_display_service, display_id) {
+            Ok(event_handles) => event_handles,
+            Err(rc) => {
+                // The surface doesn't exist yet (thus it won't finalize itself on drop), so the layer and the display it was created for must be released here
+                let _ = (layer_destroy_fn)(layer_id, application_display_service.clone());
+                let _ = application_display_service.get().close_display(display_id);
+                return Err(rc);
+            }
+        };
+        let mut surface = Self { binder, nvdrv_srv, application_display_service, width, height, buffer_data: alloc::Buffer::empty(), single_buffer_size: 0, buffer_count, slot_has_requested: [false; MAX_BUFFERS], graphic_buf: Default::default(), color_fmt, pixel_fmt, layout, display_id, layer_id, layer_destroy_fn, nvhost_fd, nvmap_fd, nvhostctrl_fd, vsync_event_handle, buffer_event_handle };
         surface.initialize()?;
         Ok(surface)
     }
 
+    // Everything done here is undone on failure, since finalize() won't be called for a surface which failed to be created
+    fn connect_binder(binder: &mut binder::Binder, application_display_service: &mem::Shared<dyn vi::IApplicationDisplayService>, display_id: vi::DisplayId) -> Result<(svc::Handle, svc::Handle)> {
+        binder.increase_refcounts()?;
+        if let Err(rc) = binder.connect(ConnectionApi::Cpu, false) {
+            let _ = binder.decrease_refcounts();
+            return Err(rc);
+        }
+
+        let vsync_event_handle = match application_display_service.get().get_display_vsync_event(display_id) {
+            Ok(vsync_event_handle) => vsync_event_handle.handle,
+            Err(rc) => {
+                let _ = binder.disconnect(ConnectionApi::Cpu, DisconnectMode::AllLocal);
+                let _ = binder.decrease_refcounts();
+                return Err(rc);
+            }
+        };
+        let buffer_event_handle = match binder.get_native_handle(dispdrv::NativeHandleType::BufferEvent) {
+            Ok(buffer_event_handle) => buffer_event_handle.handle,
+            Err(rc) => {
+                let _ = svc::close_handle(vsync_event_handle);
+                let _ = binder.disconnect(ConnectionApi::Cpu, DisconnectMode::AllLocal);
+                let _ = binder.decrease_refcounts();
+                return Err(rc);
+            }
+        };
+        Ok((vsync_event_handle, buffer_event_handle))
+    }
+
     fn do_ioctl<I: ioctl::Ioctl>(&mut self, i: &mut I) -> Result<()> {
         let fd = match I::get_fd() {
             ioctl::IoctlFd::NvHost => self.nvhost_fd,
@@ -87,7 +120,7 @@ impl Surface {
         let aligned_height = align_height(self.height);
         let stride = aligned_width;
         self.single_buffer_size = (aligned_width_bytes * aligned_height) as usize;
-        let usage = GraphicsAllocatorUsage::HardwareComposer() | GraphicsAllocatorUsage::HardwareRender() | GraphicsAllocatorUsage::HardwareTexture();
+        let usage = GraphicsAllocatorUsage::StandardFramebuffer();
         let buf_size = self.buffer_count as usize * self.single_buffer_size;
 
         let mut ioctl_create: ioctl::NvMapCreate = Default::default();
@@ -138,10 +171,12 @@ impl Surface {
         self.graphic_buf.planes[0].display_scan_format = scan_fmt;
         self.graphic_buf.planes[0].size = self.single_buffer_size;
 
+        // The binder keeps the allocation alive until it gets disconnected
+        let nvmap_handle = mem::Shared::new(NvMapHandle::new(self.nvdrv_srv.clone(), self.nvmap_fd, ioctl_create.handle));
         for i in 0..self.buffer_count {
             let mut graphic_buf_copy = self.graphic_buf;
             graphic_buf_copy.planes[0].offset = i * self.single_buffer_size as u32;
-            self.binder.set_preallocated_buffer(i as i32, graphic_buf_copy)?;
+            self.binder.set_preallocated_owned_buffer(i as i32, OwnedGraphicBuffer::new(graphic_buf_copy, nvmap_handle.clone()))?;
         }
 
         Ok(())
@@ -207,6 +242,23 @@ impl Surface {
         Ok((buf, self.single_buffer_size, slot, has_fences, fences))
     }
 
+    /// Acquires a frame to render to, dequeuing a buffer and waiting for its fences
+    /// 
+    /// The frame must be presented (see [`Frame::present`]) in order to be displayed, dropping it otherwise cancels the buffer
+    pub fn acquire_frame(&mut self) -> Result<Frame> {
+        let (buf, buf_size, slot, has_fences, fences) = self.dequeue_buffer(false)?;
+        if has_fences {
+            if let Err(rc) = self.wait_fences(fences, -1) {
+                let _ = self.binder.cancel_buffer(slot, fences);
+                return Err(rc);
+            }
+        }
+
+        let mut graphic_buf = self.graphic_buf;
+        graphic_buf.planes[0].offset = slot as u32 * self.single_buffer_size as u32;
+        Ok(Frame { surface: self, buf, buf_size, slot, graphic_buf, presented: false })
+    }
+
     /// Queues a buffer
     /// 
     /// # Arguments
@@ -218,6 +270,18 @@ impl Surface {
         qbi.swap_interval = 1;
         qbi.fences = fences;
 
+        self.queue_buffer_with_input(slot, qbi)
+    }
+
+    /// Queues a buffer with a custom input layout
+    /// 
+    /// The input (timestamp, crop, transform, etc.) is sent as-is
+    /// 
+    /// # Arguments
+    /// 
+    /// * `slot`: The buffer slot
+    /// * `qbi`: The input layout
+    pub fn queue_buffer_with_input(&mut self, slot: i32, qbi: QueueBufferInput) -> Result<()> {
         mem::flush_data_cache(self.buffer_data.ptr, self.single_buffer_size * self.buffer_count as usize);
 
         self.binder.queue_buffer(slot, qbi)?;
@@ -299,6 +363,66 @@ impl Surface {
     }
 }
 
+/// Represents a frame acquired from a [`Surface`] (see [`Surface::acquire_frame`])
+pub struct Frame<'a> {
+    surface: &'a mut Surface,
+    buf: *mut u8,
+    buf_size: usize,
+    slot: i32,
+    graphic_buf: GraphicBuffer,
+    presented: bool
+}
+
+impl<'a> Frame<'a> {
+    /// Gets the frame buffer address
+    #[inline]
+    pub fn get_buffer(&self) -> *mut u8 {
+        self.buf
+    }
+
+    /// Gets the frame buffer size
+    #[inline]
+    pub fn get_buffer_size(&self) -> usize {
+        self.buf_size
+    }
+
+    /// Gets the frame buffer slot
+    #[inline]
+    pub fn get_slot(&self) -> i32 {
+        self.slot
+    }
+
+    /// Gets the [`GraphicBuffer`] of the frame buffer
+    #[inline]
+    pub fn get_graphic_buffer(&self) -> GraphicBuffer {
+        self.graphic_buf
+    }
+
+    /// Presents the frame, queueing its buffer
+    /// 
+    /// The fences are placed in the queued input as-is, so the consumer (not the CPU) waits for them: GPU-rendered frames can be presented right after submitting the rendering work, with no CPU stall in between
+    /// 
+    /// For GPU rendering, the fence is the syncpoint (ID and value) the nvhost GPU channel returns when the work is submitted (see <https://switchbrew.org/wiki/NV_services#NVGPU_IOCTL_CHANNEL_SUBMIT_GPFIFO>), which can be wrapped via [`Fence::new`] and [`MultiFence::from_fence`]
+    /// 
+    /// # Arguments
+    /// 
+    /// * `fences`: The fences the consumer must wait for before reading the buffer (empty ones for CPU rendering)
+    pub fn present(mut self, fences: MultiFence) -> Result<()> {
+        // Even if queueing fails, the buffer mustn't be cancelled afterwards
+        self.presented = true;
+        self.surface.queue_buffer(self.slot, fences)
+    }
+}
+
+impl<'a> Drop for Frame<'a> {
+    /// Cancels the frame buffer if it wasn't presented
+    fn drop(&mut self) {
+        if !self.presented {
+            let _ = self.surface.binder.cancel_buffer(self.slot, Default::default());
+        }
+    }
+}
+
 impl Drop for Surface {
     /// Destroys the surface, closing everything it internally opened
     fn drop(&mut self) {
@@ -16,30 +16,36 @@ pub enum CommandProtocol {
     Tipc
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct ObjectInfo {
     pub handle: svc::Handle,
     pub domain_object_id: cmif::DomainObjectId,
     pub owns_handle: bool,
-    pub protocol: CommandProtocol
+    pub protocol: CommandProtocol,
+    // Client-side call timeout in nanoseconds, -1 (the default) waits indefinitely
+    pub call_timeout: i64
 }
 
 impl ObjectInfo {
     pub const fn new() -> Self {
-        Self { handle: 0, domain_object_id: 0, owns_handle: false, protocol: CommandProtocol::Cmif }
+        Self { handle: svc::INVALID_HANDLE, domain_object_id: 0, owns_handle: false, protocol: CommandProtocol::Cmif, call_timeout: -1 }
     }
 
     pub const fn from_handle(handle: svc::Handle) -> Self {
-        Self { handle, domain_object_id: 0, owns_handle: true, protocol: CommandProtocol::Cmif }
+        Self { handle, domain_object_id: 0, owns_handle: true, protocol: CommandProtocol::Cmif, call_timeout: -1 }
     }
 
     pub const fn from_domain_object_id(parent_handle: svc::Handle, domain_object_id: cmif::DomainObjectId) -> Self {
-        Self { handle: parent_handle, domain_object_id, owns_handle: false, protocol: CommandProtocol::Cmif }
+        Self { handle: parent_handle, domain_object_id, owns_handle: false, protocol: CommandProtocol::Cmif, call_timeout: -1 }
+    }
+
+    pub const fn has_call_timeout(&self) -> bool {
+        self.call_timeout >= 0
     }
 
     pub const fn is_valid(&self) -> bool {
-        self.handle != 0
+        self.handle != svc::INVALID_HANDLE
     }
 
     pub const fn is_domain(&self) -> bool {
@@ -74,6 +80,20 @@ impl ObjectInfo {
         }
         ipc_client_send_control_command!([*self; cmif::ControlRequestId::CloneCurrentObject] () => (cloned_handle: sf::MoveHandle))
     }
+
+    // Only supported by servers made with this crate (see cmif::ControlRequestId::Ping), official ones will fail with an invalid request ID
+    pub fn ping(&mut self) -> Result<()> {
+        if self.uses_tipc_protocol() {
+            return super::rc::ResultNotSupported::make_err();
+        }
+        ipc_client_send_control_command!([*self; cmif::ControlRequestId::Ping] () => ())
+    }
+}
+
+impl Default for ObjectInfo {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, ConstParamTy)]
@@ -286,6 +306,10 @@ impl CommandHeader {
         Self::decode_receive_static_type(read_bits!(10, 13, self.bits_2))
     }
 
+    pub fn set_receive_static_count(&mut self, receive_static_count: u32) {
+        write_bits!(10, 13, self.bits_2, Self::encode_receive_static_type(receive_static_count));
+    }
+
     pub const fn get_has_special_header(&self) -> bool {
         read_bits!(31, 31, self.bits_2) != 0
     }
@@ -404,6 +428,9 @@ impl DataWalker {
     }
 }
 
+// Size of the (TLS) message buffer, see thread::ThreadLocalRegion
+pub const MSG_BUFFER_SIZE: usize = 0x100;
+
 #[inline(always)]
 pub fn get_msg_buffer() -> *mut u8 {
     unsafe {
@@ -444,6 +471,7 @@ pub struct CommandContent {
     pub data_offset: *mut u8,
     pub data_words_offset: *mut u8,
     pub objects_offset: *mut u8,
+    pub token: u32,
     copy_handles: ArrayVec<[svc::Handle; MAX_COUNT]>,
     move_handles: ArrayVec<[svc::Handle; MAX_COUNT]>,
     objects: ArrayVec<[cmif::DomainObjectId; MAX_COUNT]>,
@@ -452,7 +480,7 @@ pub struct CommandContent {
 
 impl CommandContent {
     pub fn empty() -> Self {
-        Self { send_process_id: false, process_id: 0, data_size: 0, data_offset: ptr::null_mut(), data_words_offset: ptr::null_mut(), objects_offset: ptr::null_mut(), copy_handles: ArrayVec::new(), move_handles: ArrayVec::new(), objects: ArrayVec::new(), out_pointer_sizes: ArrayVec::new() }
+        Self { send_process_id: false, process_id: 0, data_size: 0, data_offset: ptr::null_mut(), data_words_offset: ptr::null_mut(), objects_offset: ptr::null_mut(), token: 0, copy_handles: ArrayVec::new(), move_handles: ArrayVec::new(), objects: ArrayVec::new(), out_pointer_sizes: ArrayVec::new() }
     }
     
     fn add_copy_handle(&mut self, handle: svc::Handle) -> Result<()> {
@@ -479,7 +507,7 @@ impl CommandContent {
     pub fn add_domain_object(&mut self, domain_object_id: cmif::DomainObjectId) -> Result<()> {
         match self.objects.try_push(domain_object_id) {
             Ok(()) => Ok(()),
-            Err(_) => rc::ResultDomainObjectsFull::make_err()
+            Err(_) => rc::ResultTooManyHandles::make_err()
         }
     }
 
@@ -521,17 +549,19 @@ impl CommandContent {
         Ok(handle)
     }
 
+    // The HIPC special header allows up to 15 copy/move handles (4-bit counts), thus the actual limit is our storage capacity (MAX_COUNT)
     fn push_copy_handle(&mut self, handle: svc::Handle) -> Result<()> {
         match self.copy_handles.try_push(handle) {
             Ok(()) => Ok(()),
-            Err(_) => rc::ResultCopyHandlesFull::make_err()
+            Err(_) => rc::ResultTooManyHandles::make_err()
         }
     }
 
+    // Same as above
     fn push_move_handle(&mut self, handle: svc::Handle) -> Result<()> {
         match self.move_handles.try_push(handle) {
             Ok(()) => Ok(()),
-            Err(_) => rc::ResultMoveHandlesFull::make_err()
+            Err(_) => rc::ResultTooManyHandles::make_err()
         }
     }
 
@@ -552,7 +582,7 @@ impl CommandContent {
     pub fn push_domain_object(&mut self, domain_object_id: cmif::DomainObjectId) -> Result<()> {
         match self.objects.try_push(domain_object_id) {
             Ok(()) => Ok(()),
-            Err(_) => rc::ResultDomainObjectsFull::make_err()
+            Err(_) => rc::ResultTooManyHandles::make_err()
         }
     }
 }
@@ -562,6 +592,7 @@ pub struct CommandContext {
     pub in_params: CommandContent,
     pub out_params: CommandContent,
     send_statics: ArrayVec<[SendStaticDescriptor; MAX_COUNT]>,
+    response_send_statics: ArrayVec<[SendStaticDescriptor; MAX_COUNT]>,
     receive_statics: ArrayVec<[ReceiveStaticDescriptor; MAX_COUNT]>,
     send_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
     receive_buffers: ArrayVec<[BufferDescriptor; MAX_COUNT]>,
@@ -575,7 +606,7 @@ pub struct CommandContext {
 
 impl CommandContext {
     pub fn empty() -> Self {
-        Self { object_info: ObjectInfo::new(), in_params: CommandContent::empty(), out_params: CommandContent::empty(), send_statics: ArrayVec::new(), receive_statics: ArrayVec::new(), send_buffers: ArrayVec::new(), receive_buffers: ArrayVec::new(), exchange_buffers: ArrayVec::new(), pointer_buffer: core::ptr::null_mut(), in_pointer_buffer_offset: 0, out_pointer_buffer_offset: 0, pointer_size_walker: DataWalker::empty(), pointer_size_walker_initialized: false }
+        Self { object_info: ObjectInfo::new(), in_params: CommandContent::empty(), out_params: CommandContent::empty(), send_statics: ArrayVec::new(), response_send_statics: ArrayVec::new(), receive_statics: ArrayVec::new(), send_buffers: ArrayVec::new(), receive_buffers: ArrayVec::new(), exchange_buffers: ArrayVec::new(), pointer_buffer: core::ptr::null_mut(), in_pointer_buffer_offset: 0, out_pointer_buffer_offset: 0, pointer_size_walker: DataWalker::empty(), pointer_size_walker_initialized: false }
     }
 
     pub fn new_client(object_info: ObjectInfo) -> Self {
@@ -642,12 +673,13 @@ impl CommandContext {
     }
 
     pub fn add_buffer<const A: BufferAttribute, T>(&mut self, buffer: &sf::Buffer<A, T>) -> Result<()> {
-        let is_in = A.contains(BufferAttribute::In());
-        let is_out = A.contains(BufferAttribute::Out());
+        let attrs = buffer.get_attributes();
+        let is_in = attrs.contains(BufferAttribute::In());
+        let is_out = attrs.contains(BufferAttribute::Out());
         let buf_addr = buffer.get_address();
         let buf_size = buffer.get_size();
 
-        if A.contains(BufferAttribute::AutoSelect()) {
+        if attrs.contains(BufferAttribute::AutoSelect()) {
             if self.pointer_buffer.is_null() {
                 self.pointer_buffer = self.object_info.query_pointer_buffer_size()? as *mut u8;
             }
@@ -685,23 +717,23 @@ impl CommandContext {
                 }
             }
         }
-        else if A.contains(BufferAttribute::Pointer()) {
+        else if attrs.contains(BufferAttribute::Pointer()) {
             if is_in {
                 self.add_send_static(SendStaticDescriptor::new(buf_addr, buf_size, self.send_statics.len() as u32))?;
             }
             else if is_out {
                 self.add_receive_static(ReceiveStaticDescriptor::new(buf_addr, buf_size))?;
-                if !A.contains(BufferAttribute::FixedSize()) {
+                if !attrs.contains(BufferAttribute::FixedSize()) {
                     self.in_params.add_out_pointer_size(buf_size as u16)?;
                 }
             }
         }
-        else if A.contains(BufferAttribute::MapAlias()) {
+        else if attrs.contains(BufferAttribute::MapAlias()) {
             let mut flags = BufferFlags::Normal;
-            if A.contains(BufferAttribute::MapTransferAllowsNonSecure()) {
+            if attrs.contains(BufferAttribute::MapTransferAllowsNonSecure()) {
                 flags = BufferFlags::NonSecure;
             }
-            else if A.contains(BufferAttribute::MapTransferAllowsNonDevice()) {
+            else if attrs.contains(BufferAttribute::MapTransferAllowsNonDevice()) {
                 flags = BufferFlags::NonDevice;
             }
             let buf_desc = BufferDescriptor::new(buf_addr, buf_size, flags);
@@ -722,6 +754,74 @@ impl CommandContext {
         Ok(())
     }
 
+    /// Registers an out pointer buffer to be sent back (as a send static) once the response is written
+    /// 
+    /// Handlers may freely write into the buffer during their body, the corresponding descriptor is only emitted on the response phase (see [`CommandContext::push_response_send_statics`])
+    /// 
+    /// # Arguments
+    /// 
+    /// * `buffer`: The out pointer buffer
+    pub fn add_response_out_pointer_buffer<const A: BufferAttribute, T>(&mut self, buffer: &sf::Buffer<A, T>) -> Result<()> {
+        let send_static = SendStaticDescriptor::new(buffer.get_address(), buffer.get_size(), self.response_send_statics.len() as u32);
+        match self.response_send_statics.try_push(send_static) {
+            Ok(()) => Ok(()),
+            Err(_) => rc::ResultSendStaticsFull::make_err()
+        }
+    }
+
+    /// Moves the registered out pointer buffers into the send statics which will be written on the response
+    /// 
+    /// Note that already present send statics are kept, so handlers still manually adding `InPointer` buffers (the old workaround) keep working
+    pub fn push_response_send_statics(&mut self) -> Result<()> {
+        for response_send_static in self.response_send_statics.drain(..) {
+            if self.send_statics.try_push(response_send_static).is_err() {
+                return rc::ResultSendStaticsFull::make_err();
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets whether the (parsed) request makes use of pointer buffers (thus whether it contains send statics or receive statics)
+    pub fn uses_pointer_buffer(&self) -> bool {
+        !self.send_statics.is_empty() || !self.receive_statics.is_empty()
+    }
+
+    /// Checks that the response (with the current out params, descriptors and handles) fits in the message buffer
+    /// 
+    /// This must be checked before writing the response, since an oversized response would overrun the message buffer
+    pub fn ensure_response_fits_msg_buffer(&self) -> Result<()> {
+        let data_size = match self.object_info.protocol {
+            CommandProtocol::Cmif => {
+                let mut data_size = DATA_PADDING as usize + mem::size_of::<cmif::DataHeader>() + self.out_params.data_size as usize;
+                if self.object_info.is_domain() {
+                    data_size += mem::size_of::<cmif::DomainOutDataHeader>() + mem::size_of::<cmif::DomainObjectId>() * self.out_params.objects.len();
+                }
+                (data_size + 1) & !1
+            },
+            CommandProtocol::Tipc => mem::size_of::<ResultCode>() + self.out_params.data_size as usize
+        };
+        let data_word_count = (data_size + 3) / 4;
+
+        let mut response_size = mem::size_of::<CommandHeader>();
+        let has_special_header = self.out_params.send_process_id || !self.out_params.copy_handles.is_empty() || !self.out_params.move_handles.is_empty();
+        if has_special_header {
+            response_size += mem::size_of::<CommandSpecialHeader>();
+            if self.out_params.send_process_id {
+                response_size += mem::size_of::<u64>();
+            }
+            response_size += mem::size_of::<svc::Handle>() * (self.out_params.copy_handles.len() + self.out_params.move_handles.len());
+        }
+        if self.object_info.uses_cmif_protocol() {
+            response_size += mem::size_of::<SendStaticDescriptor>() * self.send_statics.len();
+            response_size += mem::size_of::<ReceiveStaticDescriptor>() * self.receive_statics.len();
+        }
+        response_size += mem::size_of::<BufferDescriptor>() * (self.send_buffers.len() + self.receive_buffers.len() + self.exchange_buffers.len());
+        response_size += data_word_count * mem::size_of::<u32>();
+
+        result_return_if!(response_size > MSG_BUFFER_SIZE, rc::ResultOutDataTooLarge);
+        Ok(())
+    }
+
     fn pop_send_static(&mut self) -> Result<SendStaticDescriptor> {
         match self.send_statics.pop_at(0) {
             Some(send_static) => Ok(send_static),
@@ -757,11 +857,29 @@ impl CommandContext {
         }
     }
 
+    // Client-provided addresses aren't guaranteed to be suitably aligned for T, and typed accesses to misaligned buffers would be UB
     pub fn pop_buffer<const A: BufferAttribute, T>(&mut self, raw_data_walker: &mut DataWalker) -> Result<sf::Buffer<A, T>> {
-        let is_in = A.contains(BufferAttribute::In());
-        let is_out = A.contains(BufferAttribute::Out());
+        self.pop_buffer_with_transfer_mode(raw_data_walker, None)
+    }
 
-        if A.contains(BufferAttribute::AutoSelect()) {
+    /// Same as [`CommandContext::pop_buffer`] but popping the buffer with the given transfer mode instead of the one specified by its [`BufferAttribute`]s (if any)
+    /// 
+    /// This is the counterpart of client buffers overriden via [`sf::Buffer::with_transfer_mode`]
+    pub fn pop_buffer_with_transfer_mode<const A: BufferAttribute, T>(&mut self, raw_data_walker: &mut DataWalker, transfer_mode: Option<sf::BufferTransferMode>) -> Result<sf::Buffer<A, T>> {
+        let attrs = sf::Buffer::<A, T>::make_attributes(transfer_mode);
+        let mut buf = self.pop_buffer_impl::<A, T>(raw_data_walker, attrs)?;
+        result_return_unless!(buf.is_aligned(), rc::ResultMisalignedBuffer);
+        if let Some(transfer_mode) = transfer_mode {
+            buf = buf.with_transfer_mode(transfer_mode);
+        }
+        Ok(buf)
+    }
+
+    fn pop_buffer_impl<const A: BufferAttribute, T>(&mut self, raw_data_walker: &mut DataWalker, attrs: BufferAttribute) -> Result<sf::Buffer<A, T>> {
+        let is_in = attrs.contains(BufferAttribute::In());
+        let is_out = attrs.contains(BufferAttribute::Out());
+
+        if attrs.contains(BufferAttribute::AutoSelect()) {
             if is_in {
                 if let Ok(static_desc) = self.pop_send_static() {
                     if let Ok(send_desc) = self.pop_send_buffer() {
@@ -787,14 +905,14 @@ impl CommandContext {
                 }
             }
         }
-        else if A.contains(BufferAttribute::Pointer()) {
+        else if attrs.contains(BufferAttribute::Pointer()) {
             if is_in {
                 if let Ok(static_desc) = self.pop_send_static() {
                     return Ok(sf::Buffer::new(static_desc.get_address(), static_desc.get_size()));
                 }
             }
             else if is_out {
-                let buf_size = match A.contains(BufferAttribute::FixedSize()) {
+                let buf_size = match attrs.contains(BufferAttribute::FixedSize()) {
                     true => sf::Buffer::<A, T>::get_expected_size(),
                     false => {
                         self.ensure_pointer_size_walker(raw_data_walker);
@@ -807,7 +925,7 @@ impl CommandContext {
                 return Ok(sf::Buffer::new(buf, buf_size));
             }
         }
-        else if A.contains(BufferAttribute::MapAlias()) {
+        else if attrs.contains(BufferAttribute::MapAlias()) {
             if is_in && is_out {
                 if let Ok(exch_desc) = self.pop_exchange_buffer() {
                     return Ok(sf::Buffer::new(exch_desc.get_address(), exch_desc.get_size()));
@@ -850,4 +968,7 @@ pub mod cmif;
 
 pub mod tipc;
 
-pub mod sf;
\ No newline at end of file
+pub mod sf;
+
+#[cfg(feature = "test-harness")]
+pub mod loopback;
\ No newline at end of file
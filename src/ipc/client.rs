@@ -1,6 +1,11 @@
+extern crate alloc as core_alloc;
+use core_alloc::vec::Vec;
 use super::*;
 use crate::ipc::sf;
 use crate::mem;
+use crate::mem::alloc;
+use crate::sync;
+use crate::wait;
 
 pub trait RequestCommandParameter {
     fn before_request_write(var: &Self, walker: &mut DataWalker, ctx: &mut CommandContext) -> Result<()>;
@@ -43,7 +48,9 @@ impl<const A: BufferAttribute, T> !ResponseCommandParameter<sf::Buffer<A, T>> fo
 
 impl<const M: HandleMode> RequestCommandParameter for sf::Handle<M> {
     fn before_request_write(handle: &Self, _walker: &mut DataWalker, ctx: &mut CommandContext) -> Result<()> {
-        ctx.in_params.add_handle(handle.clone())
+        ctx.in_params.add_handle(handle.clone())?;
+        handle.mark_transferred();
+        Ok(())
     }
 
     fn before_send_sync_request(_handle: &Self, _walker: &mut DataWalker, _ctx: &mut CommandContext) -> Result<()> {
@@ -95,6 +102,115 @@ impl<S: IClientObject + 'static + Sized> ResponseCommandParameter<mem::Shared<S>
     }
 }
 
+/// Represents the maximum amount of timed-out requests which can be pending at the same time (see [`send_sync_request`])
+pub const MAX_PENDING_TIMED_OUT_REQUEST_COUNT: usize = 16;
+
+struct PendingTimedOutRequest {
+    user_msg_buf: alloc::Buffer<u8>,
+    event_handle: svc::Handle
+}
+
+static mut G_PENDING_TIMED_OUT_REQUESTS: sync::Locked<Vec<PendingTimedOutRequest>> = sync::Locked::new(false, Vec::new());
+
+// The kernel won't write to the buffer of a timed-out request anymore once its event is signaled (either the late reply arrived or the request got cancelled since the session was closed), thus it can be reclaimed then
+fn reclaim_pending_timed_out_requests(pending_requests: &mut Vec<PendingTimedOutRequest>) {
+    pending_requests.retain_mut(|pending_request| {
+        if svc::wait_synchronization(&pending_request.event_handle, 1, 0).is_ok() {
+            let _ = svc::close_handle(pending_request.event_handle);
+            pending_request.user_msg_buf.release();
+            false
+        }
+        else {
+            true
+        }
+    });
+}
+
+/// Sends the request currently written on the message buffer, honoring the object's call timeout (if any)
+/// 
+/// Timed-out calls fail with [`ResultTimedOut`][`svc::rc::ResultTimedOut`]
+/// 
+/// Note that the kernel doesn't support cancelling an in-flight request: after a timeout the request is still pending on the server side, so its (page-aligned) user message buffer is kept until the kernel is done with it (the late reply arrives or the session is closed), being reclaimed on later calls with timeout
+/// 
+/// In order to bound the memory held this way, calls with timeout fail with [`ResultTooManyPendingRequests`][`rc::ResultTooManyPendingRequests`] while [`MAX_PENDING_TIMED_OUT_REQUEST_COUNT`] timed-out requests are still pending
+/// 
+/// # Arguments
+/// 
+/// * `object_info`: The object to send the request to
+pub fn send_sync_request(object_info: ObjectInfo) -> Result<()> {
+    // Loopback sessions are handled right away in-process, thus timeouts don't apply to them
+    #[cfg(feature = "test-harness")]
+    if loopback::is_loopback_handle(object_info.handle) {
+        return loopback::send_sync_request(object_info.handle);
+    }
+
+    if !object_info.has_call_timeout() {
+        return svc::send_sync_request(object_info.handle);
+    }
+
+    unsafe {
+        let mut pending_requests = G_PENDING_TIMED_OUT_REQUESTS.lock();
+        reclaim_pending_timed_out_requests(&mut pending_requests);
+        result_return_if!(pending_requests.len() >= MAX_PENDING_TIMED_OUT_REQUEST_COUNT, rc::ResultTooManyPendingRequests);
+    }
+
+    let mut user_msg_buf = alloc::Buffer::<u8>::new(alloc::PAGE_ALIGNMENT, alloc::PAGE_ALIGNMENT)?;
+    let tls_msg_buf = get_msg_buffer();
+    unsafe {
+        ptr::write_bytes(user_msg_buf.ptr, 0, alloc::PAGE_ALIGNMENT);
+        ptr::copy(tls_msg_buf, user_msg_buf.ptr, MSG_BUFFER_SIZE);
+    }
+
+    let event_handle = match svc::send_async_request_with_user_buffer(user_msg_buf.ptr, alloc::PAGE_ALIGNMENT, object_info.handle) {
+        Ok(event_handle) => event_handle,
+        Err(rc) => {
+            user_msg_buf.release();
+            return Err(rc);
+        }
+    };
+
+    if let Err(rc) = wait::wait_handles(&[event_handle], object_info.call_timeout) {
+        // The request might still be replied to, so we can't release the buffer yet (see above)
+        unsafe {
+            G_PENDING_TIMED_OUT_REQUESTS.lock().push(PendingTimedOutRequest { user_msg_buf, event_handle });
+        }
+        return Err(rc);
+    }
+    let _ = svc::close_handle(event_handle);
+
+    unsafe {
+        ptr::copy(user_msg_buf.ptr, tls_msg_buf, MSG_BUFFER_SIZE);
+    }
+    user_msg_buf.release();
+    Ok(())
+}
+
+/// Represents the default attempt count for [`call_with_retry`]
+pub const DEFAULT_RETRY_ATTEMPT_COUNT: u32 = 4;
+
+/// Calls a client command, calling it again while it fails with [`ResultRetry`][`rc::ResultRetry`] (the result services return when they're temporarily unable to handle a command)
+/// 
+/// Retrying is opt-in per call: commands called directly just return [`ResultRetry`][`rc::ResultRetry`] like any other error
+/// 
+/// The thread yields before every retry, and the last result is returned once the attempts run out
+/// 
+/// # Arguments
+/// 
+/// * `attempt_count`: The maximum amount of times the command is called (see [`DEFAULT_RETRY_ATTEMPT_COUNT`])
+/// * `call_fn`: The command call
+pub fn call_with_retry<T, F: FnMut() -> Result<T>>(attempt_count: u32, mut call_fn: F) -> Result<T> {
+    let mut attempt: u32 = 1;
+    loop {
+        match call_fn() {
+            Err(rc) if rc::ResultRetry::matches(rc) && (attempt < attempt_count) => {
+                svc::sleep_thread(0)?;
+                attempt += 1;
+            },
+            call_rc => return call_rc
+        }
+    }
+}
+
 pub trait IClientObject: sf::IObject {
     fn new(session: sf::Session) -> Self where Self: Sized;
 
@@ -110,6 +226,10 @@ pub trait IClientObject: sf::IObject {
         self.get_session().convert_to_domain()
     }
 
+    fn set_call_timeout(&mut self, timeout: i64) {
+        self.get_session().set_call_timeout(timeout)
+    }
+
     fn query_own_pointer_buffer_size(&mut self) -> Result<u16> {
         self.get_info().query_pointer_buffer_size()
     }
@@ -11,7 +11,9 @@ pub enum ControlRequestId {
     CopyFromCurrentDomain = 1,
     CloneCurrentObject = 2,
     QueryPointerBufferSize = 3,
-    CloneCurrentObjectEx = 4
+    CloneCurrentObjectEx = 4,
+    // Custom (not present in official servers), far from the official IDs to avoid collisions (like the custom IMitmQueryService command)
+    Ping = 65000
 }
 
 pub const IN_DATA_HEADER_MAGIC: u32 = u32::from_le_bytes(*b"SFCI");
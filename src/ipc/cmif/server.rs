@@ -50,6 +50,43 @@ pub fn read_command_from_msg_buffer(ctx: &mut CommandContext) -> CommandType {
     }
 }
 
+// Meant for forwarding a received request as-is (mitm): buffer descriptors (map-alias ones) already point to our mapping of the client's memory and pointer (send static) data was already copied to our pointer buffer, so both are valid to forward.
+// Receive statics, however, are copied raw from the client and thus point to the client's address space, so they are redirected to our pointer buffer (where the kernel copies the client's receive statics when we reply)
+#[inline(always)]
+pub fn redirect_receive_statics_on_msg_buffer(pointer_buffer: *mut u8, pointer_buffer_size: usize) {
+    unsafe {
+        let mut ipc_buf = get_msg_buffer();
+
+        let command_header = ipc_buf as *mut CommandHeader;
+        ipc_buf = command_header.offset(1) as *mut u8;
+
+        if (*command_header).get_receive_static_count() == 0 {
+            return;
+        }
+
+        if (*command_header).get_has_special_header() {
+            let special_header = ipc_buf as *mut CommandSpecialHeader;
+            ipc_buf = special_header.offset(1) as *mut u8;
+
+            if (*special_header).get_send_process_id() {
+                ipc_buf = ipc_buf.add(cmem::size_of::<u64>());
+            }
+
+            let handle_count = (*special_header).get_copy_handle_count() + (*special_header).get_move_handle_count();
+            ipc_buf = ipc_buf.add(handle_count as usize * cmem::size_of::<svc::Handle>());
+        }
+
+        ipc_buf = ipc_buf.add((*command_header).get_send_static_count() as usize * cmem::size_of::<SendStaticDescriptor>());
+        let buffer_count = (*command_header).get_send_buffer_count() + (*command_header).get_receive_buffer_count() + (*command_header).get_exchange_buffer_count();
+        ipc_buf = ipc_buf.add(buffer_count as usize * cmem::size_of::<BufferDescriptor>());
+        ipc_buf = ipc_buf.add((*command_header).get_data_word_count() as usize * cmem::size_of::<u32>());
+
+        // A single receive static covering our whole pointer buffer, the response's send statics will be laid out there
+        (*command_header).set_receive_static_count(0xFF);
+        *(ipc_buf as *mut ReceiveStaticDescriptor) = ReceiveStaticDescriptor::new(pointer_buffer, pointer_buffer_size);
+    }
+}
+
 #[inline(always)]
 pub fn write_command_response_on_msg_buffer(ctx: &mut CommandContext, command_type: CommandType, data_size: u32) {
     unsafe {
@@ -116,6 +153,8 @@ pub fn read_request_command_from_msg_buffer(ctx: &mut CommandContext) -> Result<
                 result_return_unless!((*data_header).magic == IN_DATA_HEADER_MAGIC, super::rc::ResultInvalidInputHeader);
 
                 rq_id = (*data_header).value;
+                // The context token of RequestWithContext commands is sent in the data header itself (unused by regular requests), so the raw data offset is the same for both
+                ctx.in_params.token = (*data_header).token;
                 data_offset = data_header.offset(1) as *mut u8;
                 ctx.in_params.data_size -= cmem::size_of::<DataHeader>() as u32;
             }
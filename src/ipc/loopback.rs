@@ -0,0 +1,189 @@
+use crate::result::*;
+use crate::svc;
+use crate::mem;
+use crate::sync;
+use crate::ipc::sf::IObject;
+use super::*;
+use super::server::{ISessionObject, ServerContext, ServerHolder, HipcManager};
+use alloc::vec::Vec;
+
+// In-process loopback transport: client sessions connected straight to a server object living in the same process, without any kernel session behind them
+// Requests are handled synchronously on the message buffer they were written to (the one the client reads the response from), thus the same param marshaling code as with real sessions is exercised (client stubs, server command shims, CMIF/domain headers...) without any svc call being made for the session
+// This is meant for testing interfaces/objects, and the following differs from real sessions:
+// - Pointer buffers aren't available (the kernel is the one copying them), commands using them are answered with ResultPointerBufferUnavailable, like servers without pointer buffer do
+// - Handles are passed as-is (the kernel would move/copy them into the server's handle table), thus copy handles aren't duplicated
+// - Process IDs aren't filled in by the kernel, the server gets whatever the client wrote
+// - Non-domain sessions can't return objects (those are real sessions), convert the session to a domain first (see sf::Session::convert_to_domain)
+// - Objects can't be cloned (see HipcManager::clone_current_object)
+// - Only the CMIF protocol is supported
+
+// Real handles never have the top bits set (the kernel's handle linear IDs are 15 bits wide), thus loopback handles can't be mistaken for them
+const LOOPBACK_HANDLE_BASE: svc::Handle = 0xFFFE0000;
+const LOOPBACK_HANDLE_MASK: svc::Handle = 0xFFFF0000;
+
+struct LoopbackSessionTable {
+    sessions: Vec<ServerHolder>,
+    next_handle_index: u32
+}
+
+impl LoopbackSessionTable {
+    const fn new() -> Self {
+        Self { sessions: Vec::new(), next_handle_index: 0 }
+    }
+
+    fn allocate_handle(&mut self) -> Result<svc::Handle> {
+        let handle_count = !LOOPBACK_HANDLE_MASK + 1;
+        for _ in 0..handle_count {
+            let handle = LOOPBACK_HANDLE_BASE | self.next_handle_index;
+            self.next_handle_index = (self.next_handle_index + 1) % handle_count;
+            if !self.sessions.iter().any(|session| session.info.handle == handle) {
+                return Ok(handle);
+            }
+        }
+
+        svc::rc::ResultOutOfHandles::make_err()
+    }
+
+    fn take_session(&mut self, handle: svc::Handle) -> Result<ServerHolder> {
+        match self.sessions.iter().position(|session| session.info.handle == handle) {
+            Some(session_index) => Ok(self.sessions.swap_remove(session_index)),
+            None => svc::rc::ResultInvalidHandle::make_err()
+        }
+    }
+}
+
+static mut G_LOOPBACK_SESSIONS: sync::Locked<LoopbackSessionTable> = sync::Locked::new(false, LoopbackSessionTable::new());
+
+pub fn is_loopback_handle(handle: svc::Handle) -> bool {
+    (handle & LOOPBACK_HANDLE_MASK) == LOOPBACK_HANDLE_BASE
+}
+
+// Creates a loopback session to the given object, which is owned by the session until it's closed
+pub fn connect<S: ISessionObject + 'static>(object: mem::Shared<S>) -> Result<sf::Session> {
+    let mut sessions = unsafe { G_LOOPBACK_SESSIONS.lock() };
+    let handle = sessions.allocate_handle()?;
+
+    let mut session = ServerHolder::new_session(handle, object);
+    // There's no actual handle to close when the holder is dropped
+    session.info.owns_handle = false;
+    sessions.sessions.push(session);
+
+    Ok(sf::Session::from_handle(handle))
+}
+
+// Same as above, but wrapping the session in a client object (like the ones generated for the interfaces)
+pub fn connect_client<C: client::IClientObject, S: ISessionObject + 'static>(object: mem::Shared<S>) -> Result<C> {
+    Ok(C::new(connect(object)?))
+}
+
+fn dispatch_request_command(session: &mut ServerHolder, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType) -> Result<()> {
+    let target_object = match ctx.object_info.is_domain() && !ctx.object_info.owns_handle {
+        true => match session.domain_table.as_ref().ok_or(server::rc::ResultDomainNotFound::make()).and_then(|domain_table| domain_table.get().find_domain(ctx.object_info.domain_object_id)) {
+            Ok(domain_object) => domain_object,
+            Err(rc) => {
+                cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
+                return Ok(());
+            }
+        },
+        false => session.get_server().cloned().ok_or(server::rc::ResultSignaledServerNotFound::make())?
+    };
+
+    let protocol = ctx.object_info.protocol;
+    let command_table = target_object.get().get_command_metadata_table();
+    let mut new_sessions: Vec<ServerHolder> = Vec::new();
+    let dispatch_rc = {
+        let mut server_ctx = ServerContext::new(ctx, DataWalker::empty(), session.domain_table.clone(), &mut new_sessions);
+        match command_table.iter().find(|command| command.matches_protocol(rq_id, protocol)) {
+            Some(command) => target_object.get().call_self_server_command(command.command_fn, protocol, &mut server_ctx),
+            None => target_object.get().on_unknown_command(rq_id, &mut server_ctx)
+        }
+    };
+
+    if let Err(rc) = dispatch_rc {
+        cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
+    }
+
+    // Objects returned by non-domain sessions are sessions nobody would serve here, dropping them closes them (see above)
+    drop(new_sessions);
+    Ok(())
+}
+
+fn dispatch_control_command(session: &mut ServerHolder, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType) {
+    // No pointer buffer and no clones (see above)
+    let mut hipc_manager = HipcManager::new(session, 0, Some(0));
+    let command_table = hipc_manager.get_command_metadata_table();
+    match command_table.iter().find(|command| command.matches(rq_id)) {
+        Some(command) => {
+            let mut unused_new_sessions: Vec<ServerHolder> = Vec::new();
+            let mut server_ctx = ServerContext::new(ctx, DataWalker::empty(), None, &mut unused_new_sessions);
+            if let Err(rc) = hipc_manager.call_self_server_command(command.command_fn, CommandProtocol::Cmif, &mut server_ctx) {
+                cmif::server::write_control_command_response_on_msg_buffer(ctx, rc, command_type);
+            }
+        },
+        None => cmif::server::write_control_command_response_on_msg_buffer(ctx, cmif::rc::ResultInvalidCommandRequestId::make(), command_type)
+    };
+}
+
+// Same as what ServerManager does with a received request, the response being written on the same message buffer
+fn process_request(session: &mut ServerHolder) -> Result<()> {
+    let mut ctx = CommandContext::new_server(session.info, core::ptr::null_mut());
+    let command_type = cmif::server::read_command_from_msg_buffer(&mut ctx);
+    match command_type {
+        cmif::CommandType::Request | cmif::CommandType::RequestWithContext => {
+            let (rq_id, domain_command_type, domain_object_id) = cmif::server::read_request_command_from_msg_buffer(&mut ctx)?;
+            if ctx.uses_pointer_buffer() {
+                cmif::server::write_request_command_response_on_msg_buffer(&mut ctx, server::rc::ResultPointerBufferUnavailable::make(), command_type);
+                return Ok(());
+            }
+
+            let mut object_info = session.info;
+            if object_info.is_domain() {
+                object_info.domain_object_id = domain_object_id;
+                object_info.owns_handle = session.info.domain_object_id == domain_object_id;
+            }
+            ctx.object_info = object_info;
+
+            match (domain_command_type, object_info.is_domain()) {
+                (cmif::DomainCommandType::Invalid, false) | (cmif::DomainCommandType::SendMessage, true) => dispatch_request_command(session, &mut ctx, rq_id, command_type)?,
+                (cmif::DomainCommandType::Invalid, true) => cmif::server::write_request_command_response_on_msg_buffer(&mut ctx, server::rc::ResultInvalidDomainCommandType::make(), command_type),
+                (_, false) => cmif::server::write_request_command_response_on_msg_buffer(&mut ctx, server::rc::ResultNotDomain::make(), command_type),
+                (cmif::DomainCommandType::Close, true) => {
+                    // The base domain object is closed along with the session itself (see close_handle)
+                    if !object_info.owns_handle {
+                        let close_rc = session.domain_table.as_ref().ok_or(server::rc::ResultDomainNotFound::make()).and_then(|domain_table| domain_table.get().deallocate_domain(domain_object_id));
+                        if let Err(rc) = close_rc {
+                            cmif::server::write_request_command_response_on_msg_buffer(&mut ctx, rc, command_type);
+                        }
+                    }
+                }
+            };
+        },
+        cmif::CommandType::Control | cmif::CommandType::ControlWithContext => {
+            let rq_id = cmif::server::read_control_command_from_msg_buffer(&mut ctx)? as u32;
+            dispatch_control_command(session, &mut ctx, rq_id, command_type);
+        },
+        // The session is actually torn down once the client closes its handle (see close_handle)
+        cmif::CommandType::Close => cmif::server::write_close_command_response_on_msg_buffer(&mut ctx),
+        _ => return server::rc::ResultInvalidCommandType::make_err()
+    };
+
+    Ok(())
+}
+
+// Loopback counterpart of svc::send_sync_request (see client::send_sync_request)
+pub(crate) fn send_sync_request(handle: svc::Handle) -> Result<()> {
+    // The session is taken out of the table while the command runs, so that the lock isn't held meanwhile (handlers may use other loopback sessions)
+    let mut session = unsafe { G_LOOPBACK_SESSIONS.lock().take_session(handle)? };
+    let process_rc = process_request(&mut session);
+    unsafe {
+        G_LOOPBACK_SESSIONS.lock().sessions.push(session);
+    }
+    process_rc
+}
+
+// Loopback counterpart of svc::close_handle (see sf::Session::close), closing the session like the server would on the client closing it
+pub(crate) fn close_handle(handle: svc::Handle) -> Result<()> {
+    let session = unsafe { G_LOOPBACK_SESSIONS.lock().take_session(handle)? };
+    session.run_close_hooks();
+    Ok(())
+}
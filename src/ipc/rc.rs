@@ -19,5 +19,10 @@ result_define_subgroup!(rc::RESULT_MODULE, RESULT_SUBMODULE => {
     InvalidReceiveBufferCount: 14,
     InvalidExchangeBufferCount: 15,
     InvalidBufferAttributes: 16,
-    InvalidProtocol: 17
+    InvalidProtocol: 17,
+    OutDataTooLarge: 18,
+    TooManyHandles: 19,
+    MisalignedBuffer: 21,
+    Retry: 22,
+    TooManyPendingRequests: 23
 });
\ No newline at end of file
@@ -1,5 +1,6 @@
 use crate::result::*;
 use crate::svc;
+use crate::arm;
 use crate::wait;
 use crate::ipc::sf::IObject;
 use crate::ipc::sf::hipc::IHipcManager;
@@ -21,6 +22,9 @@ use crate::service::sm::IUserInterface;
 
 pub mod rc;
 
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+
 // TODO: TIPC support, implement remaining control commands
 
 const MAX_COUNT: usize = wait::MAX_OBJECT_COUNT as usize;
@@ -29,17 +33,54 @@ pub struct ServerContext<'a> {
     pub ctx: &'a mut CommandContext,
     pub raw_data_walker: DataWalker,
     pub domain_table: Option<mem::Shared<DomainTable>>,
-    pub new_sessions: &'a mut Vec<ServerHolder>
+    pub new_sessions: &'a mut Vec<ServerHolder>,
+    mitm_process_info: Option<sm::mitm::MitmProcessInfo>,
+    user_data: Option<mem::Shared<dyn core::any::Any>>
 }
 
 impl<'a> ServerContext<'a> {
     pub const fn new(ctx: &'a mut CommandContext, raw_data_walker: DataWalker, domain_table: Option<mem::Shared<DomainTable>>, new_sessions: &'a mut Vec<ServerHolder>) -> Self {
-        Self { ctx, raw_data_walker, domain_table, new_sessions }
+        Self { ctx, raw_data_walker, domain_table, new_sessions, mitm_process_info: None, user_data: None }
+    }
+
+    // Process info of the client which opened the session, as received when the session was accepted (None for non-mitm sessions)
+    pub fn get_mitm_process_info(&self) -> Option<sm::mitm::MitmProcessInfo> {
+        self.mitm_process_info
+    }
+
+    // Context token sent by the client (only meaningful for RequestWithContext commands)
+    pub fn get_context_token(&self) -> u32 {
+        self.ctx.in_params.token
+    }
+
+    // Data attached to the session when it was accepted (see ServerManager::set_session_user_data_fn), which can be downcast to the concrete type:
+    // if let Some(data) = ctx.get_user_data() { if let Some(my_data) = data.get().downcast_ref::<MyData>() { ... } }
+    pub fn get_user_data(&self) -> Option<mem::Shared<dyn core::any::Any>> {
+        self.user_data.clone()
+    }
+
+    // Creates a new session to the given object, returning its client handle so that it can be handed to a third party (for instance, passing a self-hosted object through another service's command)
+    // The server side is registered along with the sessions accepted by the manager, this is what returning objects does on non-domain sessions (see ResponseCommandParameter for Shared)
+    // The handle is closed if it's dropped without being sent, which closes the new session too
+    pub fn create_handle_to(&mut self, obj: mem::Shared<dyn ISessionObject>) -> Result<sf::MoveHandle> {
+        let (server_handle, client_handle) = svc::create_session(false, 0)?;
+        self.new_sessions.push(ServerHolder::new_session(server_handle, obj));
+        Ok(sf::MoveHandle::take(client_handle))
+    }
+
+    // Escape hatch for commands which don't fit the typed parameter system: this is the current thread's TLS message buffer, which both the parsed request and the response will be written to.
+    // Any pointers held by the context (data offsets, the raw data walker...) point into this same buffer, so writes here are visible to (and may be overwritten by) the regular response writing.
+    // The slice must not be kept beyond the handler, since the buffer is reused for every message received on this thread.
+    pub fn raw_message_buffer(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(get_msg_buffer(), MSG_BUFFER_SIZE)
+        }
     }
 }
 
-pub type CommandFn = fn(&mut dyn IObject, CommandProtocol, &mut ServerContext) -> Result<()>;
-pub type CommandSpecificFn<T> = fn(&mut T, CommandProtocol, &mut ServerContext) -> Result<()>;
+// Type-erased command shims (generated by the interface macros), the first argument is the object the command table was obtained from
+// Objects can't be downcast through Any (they're not necessarily 'static, like HipcManager), thus the object pointer is erased instead of transmuting between fn pointer types
+pub type CommandFn = unsafe fn(*mut (), CommandProtocol, &mut ServerContext) -> Result<()>;
 
 pub trait RequestCommandParameter<O> {
     fn after_request_read(ctx: &mut ServerContext) -> Result<O>;
@@ -73,10 +114,8 @@ impl<const A: BufferAttribute, T> RequestCommandParameter<sf::Buffer<A, T>> for
         let buf = ctx.ctx.pop_buffer(&mut ctx.raw_data_walker)?;
 
         if A.contains(BufferAttribute::Out()) && A.contains(BufferAttribute::Pointer()) {
-            // For Out(Fixed)Pointer buffers, we need to send them back as InPointer
-            // Note: since buffers can't be out params in this command param system, we need to send them back this way
-            let in_ptr_buf = sf::InPointerBuffer::<u8>::from_other(&buf);
-            ctx.ctx.add_buffer(&in_ptr_buf)?;
+            // Out(Fixed)Pointer buffers are sent back as send statics, but only once the response is written (the handler fills them in the meantime)
+            ctx.ctx.add_response_out_pointer_buffer(&buf)?;
         }
 
         Ok(buf)
@@ -93,7 +132,9 @@ impl<const M: HandleMode> RequestCommandParameter<sf::Handle<M>> for sf::Handle<
 
 impl<const M: HandleMode> ResponseCommandParameter for sf::Handle<M> {
     fn before_response_write(handle: &Self, ctx: &mut ServerContext) -> Result<()> {
-        ctx.ctx.out_params.push_handle(handle.clone())
+        ctx.ctx.out_params.push_handle(handle.clone())?;
+        handle.mark_transferred();
+        Ok(())
     }
 
     fn after_response_write(_handle: &Self, _ctx: &mut ServerContext) -> Result<()> {
@@ -146,7 +187,38 @@ impl<S: sf::IObject + ?Sized> ResponseCommandParameter for mem::Shared<S> {
     }
 }
 
-pub trait ISessionObject: sf::IObject {}
+pub trait ISessionObject: sf::IObject {
+    // Called for commands which aren't present in the command metadata table (non-mitm services only, mitm ones forward them to the original service)
+    // On success the response is expected to have been written by the handler, like regular commands do, otherwise the returned result is sent as the response
+    fn on_unknown_command(&mut self, _rq_id: u32, _ctx: &mut ServerContext) -> Result<()> {
+        cmif::rc::ResultInvalidCommandRequestId::make_err()
+    }
+
+    // Leaf objects (which never return subobjects) may refuse being converted to a domain, since it would just waste an object ID
+    fn allows_domain_conversion(&self) -> bool {
+        true
+    }
+
+    // Called when a session of a (non-mitm) service is closed: objects which are expensive to construct may reset their state here and return true, so that they get reused for newly accepted sessions instead of constructing new ones
+    fn reset_for_reuse(&mut self) -> bool {
+        false
+    }
+
+    // Called when the session is closed, either by the client or by the server (like idle sessions being reaped, see ServerManager::set_session_idle_timeout)
+    // Domain subobjects get this called too, both when closed individually and when their owning session gets closed
+    fn on_session_closed(&mut self) {
+    }
+
+    // Called right before the object gets dropped by the server (when the last holder referencing it is dropped), thus before any of its fields are dropped
+    // Objects owning other sessions (sub-services) may close them here in the order they need, instead of relying on the field declaration order (wrapping them in ManuallyDrop and dropping them from the Drop impl is the equivalent for objects dropped outside the server)
+    fn pre_drop(&mut self) {
+    }
+
+    // Allows runtime downcasts of (type-erased) session objects, objects need to opt in via ipc_server_session_object_impl_as_any!() since this can't be provided by default
+    fn as_any(&self) -> Option<&dyn core::any::Any> {
+        None
+    }
+}
 
 pub trait IServerObject: ISessionObject {
     fn new() -> Self where Self: Sized;
@@ -168,6 +240,58 @@ fn create_mitm_server_object_impl<S: IMitmServerObject + 'static>(info: sm::mitm
     mem::Shared::new(S::new(info))
 }
 
+pub type ObjectFactoryFn = fn() -> mem::Shared<dyn ISessionObject>;
+
+struct ObjectRegistryEntry {
+    name: &'static str,
+    factory: ObjectFactoryFn,
+    object: Option<mem::Shared<dyn ISessionObject>>
+}
+
+// Allows services to share objects between them: returning a looked-up object from a command hands out a new session to the same (singleton) object
+// Note that the registry holds strong references, thus created objects live as long as the registry itself (or until they are unregistered)
+pub struct ObjectRegistry {
+    entries: Vec<ObjectRegistryEntry>
+}
+
+impl ObjectRegistry {
+    pub const fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    pub fn register(&mut self, name: &'static str, factory: ObjectFactoryFn) -> Result<()> {
+        result_return_if!(self.entries.iter().any(|entry| entry.name == name), rc::ResultObjectAlreadyRegistered);
+
+        self.entries.push(ObjectRegistryEntry { name, factory, object: None });
+        Ok(())
+    }
+
+    pub fn register_object<S: IServerObject + 'static>(&mut self, name: &'static str) -> Result<()> {
+        self.register(name, create_server_object_impl::<S>)
+    }
+
+    pub fn unregister(&mut self, name: &'static str) -> Result<()> {
+        match self.entries.iter().position(|entry| entry.name == name) {
+            Some(index) => {
+                self.entries.remove(index);
+                Ok(())
+            },
+            None => rc::ResultObjectNotRegistered::make_err()
+        }
+    }
+
+    // The object is created on the first lookup, and the same instance is returned afterwards
+    pub fn lookup(&mut self, name: &'static str) -> Result<mem::Shared<dyn ISessionObject>> {
+        let entry = self.entries.iter_mut().find(|entry| entry.name == name).ok_or(rc::ResultObjectNotRegistered::make())?;
+        let object = match entry.object.take() {
+            Some(object) => object,
+            None => (entry.factory)()
+        };
+        entry.object = Some(object.clone());
+        Ok(object)
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u8)]
 pub enum WaitHandleType {
@@ -175,27 +299,86 @@ pub enum WaitHandleType {
     Session
 }
 
-pub struct DomainTable {
-    pub table: Vec<cmif::DomainObjectId>,
-    pub domains: Vec<ServerHolder>,
+// What a holder was registered as, which determines what needs to be done with sm when it's closed
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ServerKind {
+    Service(sm::ServiceName),
+    MitmService(sm::ServiceName),
+    NamedPort,
+    Session
 }
 
-impl DomainTable {
-    pub fn new() -> Self {
-        Self { table: Vec::new(), domains: Vec::new() }
+impl ServerKind {
+    // Named ports and sessions have no service name, thus an empty one is returned for them
+    pub fn get_service_name(&self) -> sm::ServiceName {
+        match *self {
+            Self::Service(service_name) | Self::MitmService(service_name) => service_name,
+            Self::NamedPort | Self::Session => sm::ServiceName::empty()
+        }
     }
+}
 
-    pub fn allocate_id(&mut self) -> Result<cmif::DomainObjectId> {
+#[derive(Copy, Clone)]
+pub struct SignaledInfo {
+    pub handle: svc::Handle,
+    pub service_name: sm::ServiceName,
+    pub handle_type: WaitHandleType
+}
+
+// What processing a signaled handle resulted in, meant for supervisor loops reacting to activity
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ProcessEvent {
+    NewSession { count: usize },
+    Request { handle: svc::Handle, rq_id: u32 },
+    ControlCommand { handle: svc::Handle, rq_id: u32 },
+    SessionClosed { handle: svc::Handle },
+    // The wait was interrupted from another thread (see ServerManager::interrupt)
+    Interrupted,
+    // Nothing was done (for instance, accepting a session failed)
+    Nothing
+}
+
+// Decides which object IDs are allocated for new domain objects (see DomainTable::new_with_allocator)
+pub trait DomainIdAllocator {
+    // The returned ID must not be one of the already allocated ones
+    fn allocate(&mut self, allocated_ids: &[cmif::DomainObjectId]) -> Result<cmif::DomainObjectId>;
+}
+
+// Default strategy: the lowest free ID, starting from 1
+pub struct SequentialDomainIdAllocator;
+
+impl DomainIdAllocator for SequentialDomainIdAllocator {
+    fn allocate(&mut self, allocated_ids: &[cmif::DomainObjectId]) -> Result<cmif::DomainObjectId> {
         let mut current_id: cmif::DomainObjectId = 1;
         loop {
             // Note: fix potential infinite loops here?
-            if !self.table.contains(&current_id) {
-                self.table.push(current_id);
+            if !allocated_ids.contains(&current_id) {
                 return Ok(current_id);
             }
             current_id += 1;
         }
     }
+}
+
+pub struct DomainTable {
+    pub table: Vec<cmif::DomainObjectId>,
+    pub domains: Vec<ServerHolder>,
+    allocator: mem::Shared<dyn DomainIdAllocator>
+}
+
+impl DomainTable {
+    pub fn new() -> Self {
+        Self::new_with_allocator(mem::Shared::new(SequentialDomainIdAllocator))
+    }
+
+    pub fn new_with_allocator(allocator: mem::Shared<dyn DomainIdAllocator>) -> Self {
+        Self { table: Vec::new(), domains: Vec::new(), allocator }
+    }
+
+    pub fn allocate_id(&mut self) -> Result<cmif::DomainObjectId> {
+        let domain_object_id = self.allocator.get().allocate(&self.table)?;
+        self.allocate_specific_id(domain_object_id)
+    }
 
     pub fn allocate_specific_id(&mut self, specific_domain_object_id: cmif::DomainObjectId) -> Result<cmif::DomainObjectId> {
         if !self.table.contains(&specific_domain_object_id) {
@@ -209,16 +392,51 @@ impl DomainTable {
     pub fn find_domain(&mut self, id: cmif::DomainObjectId) -> Result<mem::Shared<dyn ISessionObject>> {
         for holder in &self.domains {
             if holder.info.domain_object_id == id {
-                return holder.server.clone().ok_or(rc::ResultDomainNotFound::make());
+                return holder.get_server().cloned().ok_or(rc::ResultDomainNotFound::make());
             }
         }
 
         rc::ResultDomainNotFound::make_err()
     }
     
-    pub fn deallocate_domain(&mut self, domain_object_id: cmif::DomainObjectId) {
+    pub fn iter(&self) -> impl Iterator<Item = (cmif::DomainObjectId, &mem::Shared<dyn ISessionObject>)> {
+        self.domains.iter().filter_map(|holder| holder.get_server().map(|server| (holder.info.domain_object_id, server)))
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn deallocate_domain(&mut self, domain_object_id: cmif::DomainObjectId) -> Result<()> {
+        result_return_unless!(self.table.contains(&domain_object_id), rc::ResultDomainNotFound);
+
         self.table.retain(|&id| id != domain_object_id);
-        self.domains.retain(|holder| holder.info.domain_object_id != domain_object_id);
+        self.domains.retain(|holder| {
+            if holder.info.domain_object_id == domain_object_id {
+                holder.run_close_hooks();
+                false
+            }
+            else {
+                true
+            }
+        });
+        Ok(())
+    }
+
+    // Objects are closed and dropped in reverse allocation order, so that objects created from others (like files opened from a filesystem) are dropped before the objects they came from
+    pub fn deallocate_all(&mut self) {
+        self.table.clear();
+        while let Some(holder) = self.domains.pop() {
+            holder.run_close_hooks();
+            drop(holder);
+        }
+    }
+}
+
+impl Drop for DomainTable {
+    fn drop(&mut self) {
+        // Vec drops its elements in order, thus the ordered teardown is needed here too
+        self.deallocate_all();
     }
 }
 
@@ -230,35 +448,66 @@ pub struct ServerHolder {
     pub handle_type: WaitHandleType,
     pub mitm_forward_info: ObjectInfo,
     pub is_mitm_service: bool,
-    pub service_name: sm::ServiceName,
-    pub domain_table: Option<mem::Shared<DomainTable>>
+    pub kind: ServerKind,
+    pub domain_table: Option<mem::Shared<DomainTable>>,
+    pub is_adopted_service: bool,
+    pub last_activity_tick: u64,
+    pub mitm_process_info: Option<sm::mitm::MitmProcessInfo>,
+    // Framework-level per-session data (see ServerContext::get_user_data), shared with cloned sessions
+    pub user_data: Option<mem::Shared<dyn core::any::Any>>,
+    // Pointer buffer size of this session (see ServerManager::set_session_pointer_buffer_size_fn), the manager-wide one if unset
+    pub pointer_buffer_size: Option<usize>,
+    // Whether the session gets closed after being idle for too long (see ServerManager::set_session_idle_timeout), only the case for sessions accepted from server ports (and their clones)
+    // Sessions registered by other means (like the mitm query session) are legitimately idle for long periods
+    pub idle_reapable: bool,
+    // Amount of outstanding clones of the object (see HipcManager::clone_current_object), shared by the holder they were cloned from and all the clones
+    pub clone_count: Option<mem::Shared<usize>>,
+    pub is_cloned_object: bool
 }
 
 impl ServerHolder {
     pub fn new_session(handle: svc::Handle, object: mem::Shared<dyn ISessionObject>) -> Self {
-        Self { server: Some(object), info: ObjectInfo::from_handle(handle), new_server_fn: None, new_mitm_server_fn: None, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, service_name: sm::ServiceName::empty(), domain_table: None } 
+        Self { server: Some(object), info: ObjectInfo::from_handle(handle), new_server_fn: None, new_mitm_server_fn: None, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, kind: ServerKind::Session, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: false, clone_count: None, is_cloned_object: false } 
     }
 
     pub fn new_domain_session(handle: svc::Handle, domain_object_id: cmif::DomainObjectId, object: mem::Shared<dyn ISessionObject>) -> Self {
-        Self { server: Some(object), info: ObjectInfo::from_domain_object_id(handle, domain_object_id), new_server_fn: None, new_mitm_server_fn: None, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, service_name: sm::ServiceName::empty(), domain_table: None } 
+        Self { server: Some(object), info: ObjectInfo::from_domain_object_id(handle, domain_object_id), new_server_fn: None, new_mitm_server_fn: None, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, kind: ServerKind::Session, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: false, clone_count: None, is_cloned_object: false } 
     }
     
     pub fn new_server<S: IServerObject + 'static>(handle: svc::Handle, service_name: sm::ServiceName) -> Self {
-        Self { server: None, info: ObjectInfo::from_handle(handle), new_server_fn: Some(create_server_object_impl::<S>), new_mitm_server_fn: None, handle_type: WaitHandleType::Server, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, service_name, domain_table: None } 
+        Self::new_server_with_kind::<S>(handle, ServerKind::Service(service_name))
+    }
+
+    pub fn new_named_port_server<S: IServerObject + 'static>(handle: svc::Handle) -> Self {
+        Self::new_server_with_kind::<S>(handle, ServerKind::NamedPort)
+    }
+
+    fn new_server_with_kind<S: IServerObject + 'static>(handle: svc::Handle, kind: ServerKind) -> Self {
+        Self { server: None, info: ObjectInfo::from_handle(handle), new_server_fn: Some(create_server_object_impl::<S>), new_mitm_server_fn: None, handle_type: WaitHandleType::Server, mitm_forward_info: ObjectInfo::new(), is_mitm_service: false, kind, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: false, clone_count: None, is_cloned_object: false } 
+    }
+
+    pub fn new_adopted_server<S: IServerObject + 'static>(handle: svc::Handle, service_name: sm::ServiceName) -> Self {
+        let mut holder = Self::new_server::<S>(handle, service_name);
+        holder.is_adopted_service = true;
+        holder
     }
 
     pub fn new_mitm_server<S: IMitmServerObject + 'static>(handle: svc::Handle, service_name: sm::ServiceName) -> Self {
-        Self { server: None, info: ObjectInfo::from_handle(handle), new_server_fn: None, new_mitm_server_fn: Some(create_mitm_server_object_impl::<S>), handle_type: WaitHandleType::Server, mitm_forward_info: ObjectInfo::new(), is_mitm_service: true, service_name, domain_table: None } 
+        Self { server: None, info: ObjectInfo::from_handle(handle), new_server_fn: None, new_mitm_server_fn: Some(create_mitm_server_object_impl::<S>), handle_type: WaitHandleType::Server, mitm_forward_info: ObjectInfo::new(), is_mitm_service: true, kind: ServerKind::MitmService(service_name), domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: false, clone_count: None, is_cloned_object: false } 
     }
 
     pub fn make_new_session(&self, handle: svc::Handle) -> Result<Self> {
         let new_fn = self.get_new_server_fn()?;
-        Ok(Self { server: Some((new_fn)()), info: ObjectInfo::from_handle(handle), new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: self.is_mitm_service, service_name: sm::ServiceName::empty(), domain_table: None })
+        Ok(Self { server: Some((new_fn)()), info: ObjectInfo::from_handle(handle), new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: self.is_mitm_service, kind: ServerKind::Session, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: true, clone_count: None, is_cloned_object: false })
+    }
+
+    pub fn make_new_session_from_object(&self, handle: svc::Handle, object: mem::Shared<dyn ISessionObject>) -> Self {
+        Self { server: Some(object), info: ObjectInfo::from_handle(handle), new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::new(), is_mitm_service: self.is_mitm_service, kind: ServerKind::Session, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: None, user_data: None, pointer_buffer_size: None, idle_reapable: true, clone_count: None, is_cloned_object: false }
     }
 
     pub fn make_new_mitm_session(&self, handle: svc::Handle, forward_handle: svc::Handle, info: sm::mitm::MitmProcessInfo) -> Result<Self> {
         let new_mitm_fn = self.get_new_mitm_server_fn()?;
-        Ok(Self { server: Some((new_mitm_fn)(info)), info: ObjectInfo::from_handle(handle), new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::from_handle(forward_handle), is_mitm_service: self.is_mitm_service, service_name: sm::ServiceName::empty(), domain_table: None })
+        Ok(Self { server: Some((new_mitm_fn)(info)), info: ObjectInfo::from_handle(handle), new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: ObjectInfo::from_handle(forward_handle), is_mitm_service: self.is_mitm_service, kind: ServerKind::Session, domain_table: None, is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: Some(info), user_data: None, pointer_buffer_size: None, idle_reapable: true, clone_count: None, is_cloned_object: false })
     }
 
     pub fn clone_self(&self, handle: svc::Handle, forward_handle: svc::Handle) -> Result<Self> {
@@ -266,7 +515,15 @@ impl ServerHolder {
         object_info.handle = handle;
         let mut mitm_fwd_info = self.mitm_forward_info;
         mitm_fwd_info.handle = forward_handle;
-        Ok(Self { server: self.server.clone(), info: object_info, new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: mitm_fwd_info, is_mitm_service: forward_handle != 0, service_name: sm::ServiceName::empty(), domain_table: self.domain_table.clone() })
+        if let Some(clone_count) = self.clone_count.as_ref() {
+            *clone_count.get() += 1;
+        }
+        Ok(Self { server: self.server.clone(), info: object_info, new_server_fn: self.new_server_fn, new_mitm_server_fn: self.new_mitm_server_fn, handle_type: WaitHandleType::Session, mitm_forward_info: mitm_fwd_info, is_mitm_service: forward_handle != svc::INVALID_HANDLE, kind: ServerKind::Session, domain_table: self.domain_table.clone(), is_adopted_service: false, last_activity_tick: arm::get_system_tick(), mitm_process_info: self.mitm_process_info, user_data: self.user_data.clone(), pointer_buffer_size: self.pointer_buffer_size, idle_reapable: self.idle_reapable, clone_count: self.clone_count.clone(), is_cloned_object: true })
+    }
+
+    // Objects reset (see mem::Shared::reset) while still being held are treated as missing, instead of being dereferenced
+    pub fn get_server(&self) -> Option<&mem::Shared<dyn ISessionObject>> {
+        self.server.as_ref().filter(|server| !server.is_null())
     }
 
     pub fn get_new_server_fn(&self) -> Result<NewServerFn> {
@@ -304,30 +561,78 @@ impl ServerHolder {
         Ok(domain_object_id)
     }
 
-    pub fn close(&mut self) -> Result<()> {
-        if !self.service_name.is_empty() {
-            #[cfg(feature = "services")]
-            {
-                let sm = service::new_named_port_object::<sm::UserInterface>()?;
-                match self.is_mitm_service {
-                    true => sm.get().atmosphere_uninstall_mitm(self.service_name)?,
-                    false => sm.get().unregister_service(self.service_name)?
-                };
-                sm.get().detach_client(sf::ProcessId::new())?;
+    // Runs the close hook of the object and tears down its domain (if any), so that teardown is the same whether subobjects are closed one by one or along with the whole session
+    // Cloned sessions share the object and the domain table, thus they are only torn down when the last holder referencing them is closed
+    pub fn run_close_hooks(&self) {
+        if let Some(server) = self.get_server() {
+            if server.use_count() == 1 {
+                server.get().on_session_closed();
             }
         }
+        if let Some(domain_table) = self.domain_table.as_ref() {
+            if domain_table.use_count() == 1 {
+                domain_table.get().deallocate_all();
+            }
+        }
+    }
+
+    // The pointer buffer is shared by all sessions, thus a session can't be granted more than its actual size
+    pub fn get_pointer_buffer_size(&self, max_size: usize) -> usize {
+        match self.pointer_buffer_size {
+            Some(pointer_buffer_size) => pointer_buffer_size.min(max_size),
+            None => max_size
+        }
+    }
+
+    pub fn is_reclaimable(&self) -> bool {
+        // A zero-handle session holder whose object isn't referenced anywhere else has no pending work left (it won't ever be waited on again)
+        // If the object is still referenced somewhere else (for instance, by an in-flight command), the holder might just be temporarily zeroed
+        if self.info.is_valid() || (self.handle_type != WaitHandleType::Session) {
+            return false;
+        }
+
+        match self.server {
+            Some(ref server) => server.use_count() <= 1,
+            None => true
+        }
+    }
+
+    pub fn close(&mut self) -> Result<()> {
+        // Adopted services weren't registered by us, so we mustn't unregister them either
+        // Named ports and sessions have nothing to undo with sm
+        #[cfg(feature = "services")]
+        match self.kind {
+            ServerKind::Service(service_name) if !self.is_adopted_service => {
+                service::with_sm_session(|sm| sm.unregister_service(service_name))?;
+            },
+            ServerKind::MitmService(service_name) => {
+                service::with_sm_session(|sm| sm.atmosphere_uninstall_mitm(service_name))?;
+            },
+            _ => {}
+        };
 
         // Don't close our session like a normal one (like the forward session below) as we allocated the object IDs ourselves, the only thing we do have to close is the handle
-        if self.info.owns_handle {
+        if self.info.owns_handle && self.info.is_valid() {
             svc::close_handle(self.info.handle)?;
         }
         sf::Session::from(self.mitm_forward_info).close();
+
+        if self.is_cloned_object {
+            if let Some(clone_count) = self.clone_count.as_ref() {
+                *clone_count.get() = clone_count.get().saturating_sub(1);
+            }
+        }
         Ok(())
     }
 }
 
 impl Drop for ServerHolder {
     fn drop(&mut self) {
+        if let Some(server) = self.get_server() {
+            if server.use_count() == 1 {
+                server.get().pre_drop();
+            }
+        }
         self.close().unwrap();
     }
 }
@@ -335,16 +640,18 @@ impl Drop for ServerHolder {
 pub struct HipcManager<'a> {
     server_holder: &'a mut ServerHolder,
     pointer_buf_size: usize,
+    max_cloned_object_count: Option<usize>,
     pub cloned_object_server_handle: svc::Handle,
     pub cloned_object_forward_handle: svc::Handle,
     dummy_session: sf::Session
 }
 
 impl<'a> HipcManager<'a> {
-    pub fn new(server_holder: &'a mut ServerHolder, pointer_buf_size: usize) -> Self {
+    pub fn new(server_holder: &'a mut ServerHolder, pointer_buf_size: usize, max_cloned_object_count: Option<usize>) -> Self {
         Self {
             server_holder,
             pointer_buf_size,
+            max_cloned_object_count,
             cloned_object_server_handle: svc::INVALID_HANDLE,
             cloned_object_forward_handle: svc::INVALID_HANDLE,
             dummy_session: sf::Session::new()
@@ -352,7 +659,7 @@ impl<'a> HipcManager<'a> {
     }
 
     pub fn has_cloned_object(&self) -> bool {
-        self.cloned_object_server_handle != 0
+        self.cloned_object_server_handle != svc::INVALID_HANDLE
     }
 
     pub fn clone_object(&self) -> Result<ServerHolder> {
@@ -370,6 +677,10 @@ impl<'a> sf::IObject for HipcManager<'a> {
 
 impl<'a> IHipcManager for HipcManager<'a> {
     fn convert_current_object_to_domain(&mut self) -> Result<cmif::DomainObjectId> {
+        if let Some(server) = self.server_holder.get_server() {
+            result_return_unless!(server.get().allows_domain_conversion(), rc::ResultDomainConversionNotAllowed);
+        }
+
         self.server_holder.convert_to_domain()
     }
 
@@ -379,9 +690,16 @@ impl<'a> IHipcManager for HipcManager<'a> {
     }
 
     fn clone_current_object(&mut self) -> Result<sf::MoveHandle> {
+        // The object's reference count can't be used here, since the object might be referenced elsewhere too (pooled objects, in-flight commands...)
+        // Instead, clones are explicitly counted (see ServerHolder::clone_self), and closed clones are discounted
+        let clone_count = self.server_holder.clone_count.get_or_insert_with(|| mem::Shared::new(0));
+        if let Some(max_cloned_object_count) = self.max_cloned_object_count {
+            result_return_if!(*clone_count.get() >= max_cloned_object_count, rc::ResultTooManyClonedObjects);
+        }
+
         let (server_handle, client_handle) = svc::create_session(false, 0)?;
         
-        let mut forward_handle: svc::Handle = 0;
+        let mut forward_handle = svc::INVALID_HANDLE;
         if self.server_holder.is_mitm_service {
             let fwd_handle = self.server_holder.mitm_forward_info.clone_current_object()?;
             forward_handle = fwd_handle.handle;
@@ -400,6 +718,11 @@ impl<'a> IHipcManager for HipcManager<'a> {
         // The tag value is unused anyways :P
         self.clone_current_object()
     }
+
+    fn ping(&mut self) -> Result<()> {
+        // Liveness check for supervisors: this is answered by the manager loop itself, without touching the service object
+        Ok(())
+    }
 }
 
 impl<'a> ISessionObject for HipcManager<'a> {}
@@ -449,38 +772,341 @@ pub trait IMitmService: IMitmServerObject {
     fn should_mitm(info: sm::mitm::MitmProcessInfo) -> bool;
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub sleep_ns: i64
+}
+
+impl RetryPolicy {
+    // Only failures which might go away by themselves are retried, anything else (invalid service names, not being allowed, already registered services...) is final
+    // sm not being up yet (early boot) shows as its port not being found, and running out of sessions/resources might be solved by other processes releasing theirs
+    pub fn is_transient_result(rc: ResultCode) -> bool {
+        svc::rc::ResultNotFound::matches(rc)
+            || svc::rc::ResultOutOfSessions::matches(rc)
+            || svc::rc::ResultOutOfResource::matches(rc)
+            || svc::rc::ResultOutOfHandles::matches(rc)
+            || svc::rc::ResultBusy::matches(rc)
+            || svc::rc::ResultLimitReached::matches(rc)
+            || sm::rc::ResultOutOfSessions::matches(rc)
+            || super::rc::ResultRetry::matches(rc)
+    }
+
+    pub const fn new(attempts: u32, sleep_ns: i64) -> Self {
+        Self { attempts, sleep_ns }
+    }
+
+    pub const fn none() -> Self {
+        Self::new(1, 0)
+    }
+
+    pub fn run<T, F: FnMut() -> Result<T>>(&self, mut f: F) -> Result<T> {
+        let mut sleep_ns = self.sleep_ns;
+        let mut attempt: u32 = 1;
+        loop {
+            match f() {
+                Ok(t) => return Ok(t),
+                Err(rc) => {
+                    if (attempt >= self.attempts) || !Self::is_transient_result(rc) {
+                        return Err(rc);
+                    }
+                }
+            };
+
+            // A zero timeout just yields to other threads, which is enough when sm is merely busy
+            svc::sleep_thread(sleep_ns)?;
+            sleep_ns = sleep_ns.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+pub type AcceptErrorFn = fn(svc::Handle, ResultCode);
+
+pub type SessionUserDataFn = fn(&ServerHolder) -> Option<mem::Shared<dyn core::any::Any>>;
+
+pub type SessionPointerBufferSizeFn = fn(&ServerHolder) -> Option<usize>;
+
+const MAX_POOLED_OBJECT_COUNT: usize = MAX_COUNT;
+
+// Objects are pooled per server type, which is identified by its object creation function
+struct PooledObject {
+    new_server_fn: NewServerFn,
+    object: mem::Shared<dyn ISessionObject>
+}
+
+impl PooledObject {
+    #[inline]
+    fn is_from(&self, new_server_fn: NewServerFn) -> bool {
+        self.new_server_fn as usize == new_server_fn as usize
+    }
+}
+
 // TODO: use const generics to reduce memory usage, like libstratosphere does?
 
+// Signals the interrupt event of a ServerManager, this is meant to be sent to other threads (see ServerManager::enable_interrupt)
+// Note that it's no longer valid once the ServerManager is dropped
+#[derive(Copy, Clone)]
+pub struct ServerInterrupter {
+    event_handle: svc::Handle
+}
+
+impl ServerInterrupter {
+    pub fn interrupt(&self) -> Result<()> {
+        svc::signal_event(self.event_handle)
+    }
+}
+
 pub struct ServerManager<const P: usize> {
     server_holders: Vec<ServerHolder>,
     wait_handles: [svc::Handle; MAX_COUNT],
-    pointer_buffer: [u8; P]
+    wait_handle_count: usize,
+    wait_handles_dirty: bool,
+    pointer_buffer: [u8; P],
+    sm_retry_policy: RetryPolicy,
+    accept_error_fn: Option<AcceptErrorFn>,
+    accept_backlog: usize,
+    session_idle_timeout_ns: Option<u64>,
+    max_cloned_object_count: Option<usize>,
+    session_user_data_fn: Option<SessionUserDataFn>,
+    session_pointer_buffer_size_fn: Option<SessionPointerBufferSizeFn>,
+    object_pool: Vec<PooledObject>,
+    interrupt_event: Option<wait::SystemEvent>,
+    zero_buffers_after_command: bool,
+    forwarded_copy_handles: Vec<svc::Handle>,
+    #[cfg(feature = "watchdog")]
+    watchdog: Option<mem::Shared<watchdog::CommandWatchdog>>
 }
 
 impl<const P: usize> ServerManager<P> {
     pub fn new() -> Result<Self> {
-        Ok(Self { server_holders: Vec::new(), wait_handles: [0; MAX_COUNT], pointer_buffer: [0; P] })
+        Ok(Self {
+            server_holders: Vec::new(),
+            wait_handles: [0; MAX_COUNT],
+            wait_handle_count: 0,
+            wait_handles_dirty: true,
+            pointer_buffer: [0; P],
+            sm_retry_policy: RetryPolicy::none(),
+            accept_error_fn: None,
+            accept_backlog: 1,
+            session_idle_timeout_ns: None,
+            max_cloned_object_count: None,
+            session_user_data_fn: None,
+            session_pointer_buffer_size_fn: None,
+            object_pool: Vec::new(),
+            interrupt_event: None,
+            zero_buffers_after_command: false,
+            forwarded_copy_handles: Vec::new(),
+            #[cfg(feature = "watchdog")]
+            watchdog: None
+        })
+    }
+
+    // Transient sm failures (typically while sm is still coming up during early boot) are retried according to this policy
+    pub fn set_sm_retry_policy(&mut self, policy: RetryPolicy) {
+        self.sm_retry_policy = policy;
+    }
+
+    // Session accept failures (other than invalid handles) don't stop the server, they are just reported through this function (meant for logging)
+    pub fn set_accept_error_fn(&mut self, accept_error_fn: AcceptErrorFn) {
+        self.accept_error_fn = Some(accept_error_fn);
+    }
+
+    // Maximum amount of pending sessions accepted each time a server handle gets signaled (one by default)
+    // Accepting several at once reduces latency on bursty connects, and the amount is bounded (to the wait handle limit) so that request handling isn't starved
+    pub fn set_accept_backlog(&mut self, count: usize) {
+        self.accept_backlog = count.clamp(1, MAX_COUNT);
+    }
+
+    // Sessions which don't receive any request for longer than this timeout get closed (disabled by default), reclaiming the handles of clients which went away without closing them
+    // Only sessions accepted from server ports are reaped, sessions registered through register_session (like the mitm query session) never are
+    // Note that idle sessions are only reaped when processing (see close_idle_sessions), thus waiting with a timeout (see process_with_info) is needed for them to be reaped on time
+    pub fn set_session_idle_timeout(&mut self, timeout_ns: Option<u64>) {
+        self.session_idle_timeout_ns = timeout_ns;
+    }
+
+    // Maximum amount of outstanding clones of each object (unlimited by default), further clone requests fail instead of letting a client exhaust our handles
+    pub fn set_max_cloned_object_count(&mut self, count: Option<usize>) {
+        self.max_cloned_object_count = count;
+    }
+
+    // Called for every accepted session, the returned data is attached to it (see ServerContext::get_user_data)
+    pub fn set_session_user_data_fn(&mut self, session_user_data_fn: SessionUserDataFn) {
+        self.session_user_data_fn = Some(session_user_data_fn);
+    }
+
+    // Called for every accepted session, the returned size (if any) overrides the manager-wide pointer buffer size for it (see ServerHolder::get_pointer_buffer_size)
+    // Meant for managers hosting several services, so that sessions not needing pointer buffers don't get any (commands using them fail with ResultPointerBufferUnavailable), while the ones which do can get all of it
+    // Since the pointer buffer is shared, P is the maximum size any session can get
+    pub fn set_session_pointer_buffer_size_fn(&mut self, session_pointer_buffer_size_fn: SessionPointerBufferSizeFn) {
+        self.session_pointer_buffer_size_fn = Some(session_pointer_buffer_size_fn);
+    }
+
+    // Meant for (mitm) services handling sensitive data (keys, tokens...), so that it doesn't linger in the reused buffers
+    // When enabled, the pointer buffer, the message buffer and the request backup are zeroed after every command is replied to (this is opt-in since it's a memset of all of them per command)
+    pub fn set_zero_buffers_after_command(&mut self, enabled: bool) {
+        self.zero_buffers_after_command = enabled;
+    }
+
+    // Adds an event to the wait set which other threads can signal (through the returned interrupter) to wake this manager, making process() return ProcessEvent::Interrupted
+    // Shared state the woken thread re-reads (new config, a shutdown flag...) must still be properly synchronized (sync::Locked or atomics with release stores/acquire loads): the kernel wait does synchronize, but Rust doesn't know about it
+    // The interrupt event takes one of the wait slots, thus enabling it fails with ResultWaitHandlesFull if they're all taken (and one less holder can be registered afterwards)
+    pub fn enable_interrupt(&mut self) -> Result<ServerInterrupter> {
+        if self.interrupt_event.is_none() {
+            result_return_if!(self.get_wait_handle_count() >= MAX_COUNT, rc::ResultWaitHandlesFull);
+            self.interrupt_event = Some(wait::SystemEvent::new()?);
+            self.wait_handles_dirty = true;
+        }
+
+        Ok(ServerInterrupter { event_handle: self.interrupt_event.as_ref().unwrap().server_handle })
+    }
+
+    // Same-thread counterpart of ServerInterrupter::interrupt (for instance, from a command handler)
+    pub fn interrupt(&self) -> Result<()> {
+        match self.interrupt_event.as_ref() {
+            Some(interrupt_event) => interrupt_event.signal(),
+            None => rc::ResultInterruptNotEnabled::make_err()
+        }
+    }
+
+    #[inline(always)]
+    fn is_interrupt_handle(&self, handle: svc::Handle) -> bool {
+        match self.interrupt_event.as_ref() {
+            Some(interrupt_event) => interrupt_event.client_handle == handle,
+            None => false
+        }
+    }
+
+    // Starts a watchdog thread reporting commands which run for longer than the given budget
+    #[cfg(feature = "watchdog")]
+    pub fn enable_watchdog(&mut self, budget_ns: u64, report_fn: watchdog::WatchdogReportFn) -> Result<()> {
+        self.watchdog = Some(watchdog::CommandWatchdog::new(budget_ns, report_fn)?);
+        Ok(())
     }
     
+    fn check_pointer_buffer_size(required_size: usize) -> Result<()> {
+        if required_size > 0 {
+            // Distinguish a server without any pointer buffer from one whose pointer buffer is just too small
+            result_return_if!(P == 0, rc::ResultPointerBufferUnavailable);
+            result_return_unless!(P >= required_size, rc::ResultPointerBufferTooSmall);
+        }
+        Ok(())
+    }
+
+    // Only objects which aren't referenced anywhere else (thus only by the holder being closed) and agree to be reset are pooled
+    fn recycle_session_object(&mut self, server_holder: &ServerHolder) {
+        if server_holder.is_mitm_service || server_holder.domain_table.is_some() || (self.object_pool.len() >= MAX_POOLED_OBJECT_COUNT) {
+            return;
+        }
+
+        if let (Some(new_server_fn), Some(server)) = (server_holder.new_server_fn, server_holder.get_server()) {
+            if (server.use_count() == 1) && server.get().reset_for_reuse() {
+                self.object_pool.push(PooledObject { new_server_fn, object: server.clone() });
+            }
+        }
+    }
+
+    // Only sessions owning their handle are checked, domain subobjects (sharing the handle) are closed along with their base session
+    pub fn close_idle_sessions(&mut self) {
+        let timeout_ns = match self.session_idle_timeout_ns {
+            Some(timeout_ns) => timeout_ns,
+            None => return
+        };
+
+        let cur_tick = arm::get_system_tick();
+        let idle_handles: Vec<svc::Handle> = self.server_holders.iter().filter(|server_holder| {
+            (server_holder.handle_type == WaitHandleType::Session) && server_holder.idle_reapable && server_holder.info.owns_handle && server_holder.info.is_valid() && (arm::ticks_to_nanoseconds(cur_tick.wrapping_sub(server_holder.last_activity_tick)) > timeout_ns)
+        }).map(|server_holder| server_holder.info.handle).collect();
+
+        if idle_handles.is_empty() {
+            return;
+        }
+
+        self.server_holders.retain(|server_holder| {
+            if (server_holder.handle_type == WaitHandleType::Session) && idle_handles.contains(&server_holder.info.handle) {
+                server_holder.run_close_hooks();
+                false
+            }
+            else {
+                true
+            }
+        });
+        self.wait_handles_dirty = true;
+    }
+
     #[inline(always)]
-    fn prepare_wait_handles(&mut self) -> &[svc::Handle] {
-        let mut handles_index: usize = 0;
-        for server_holder in &mut self.server_holders {
-            let server_info = server_holder.info;
-            if server_info.handle != 0 {
-                self.wait_handles[handles_index] = server_info.handle;
+    fn reclaim_zero_handle_holders(&mut self) {
+        self.server_holders.retain(|server_holder| !server_holder.is_reclaimable());
+    }
+
+    // Holders with a valid handle take a wait slot each, as does the interrupt event (if enabled)
+    fn get_wait_handle_count(&self) -> usize {
+        let interrupt_count: usize = match self.interrupt_event.is_some() {
+            true => 1,
+            false => 0
+        };
+        interrupt_count + self.server_holders.iter().filter(|server_holder| server_holder.info.is_valid()).count()
+    }
+
+    // Registered holders are rejected once every wait slot is taken, instead of failing later when preparing the wait handles
+    // The rejected holder is dropped, which undoes its registration (see ServerHolder::close)
+    fn push_server_holder(&mut self, server_holder: ServerHolder) -> Result<()> {
+        result_return_if!(self.get_wait_handle_count() >= MAX_COUNT, rc::ResultWaitHandlesFull);
+
+        self.server_holders.push(server_holder);
+        self.wait_handles_dirty = true;
+        Ok(())
+    }
+
+    // Zero-handle holders are never waited on, thus reclaiming them doesn't require rebuilding the wait handles
+    // Sessions (accepted or cloned) aren't registered through push_server_holder, thus more holders than wait slots are reported here
+    #[inline(always)]
+    fn prepare_wait_handles(&mut self) -> Result<&[svc::Handle]> {
+        // The wait handles are only rebuilt when holders were added/removed since the last time
+        if self.wait_handles_dirty {
+            let mut handles_index: usize = 0;
+            if let Some(interrupt_event) = self.interrupt_event.as_ref() {
+                self.wait_handles[handles_index] = interrupt_event.client_handle;
                 handles_index += 1;
             }
+            for server_holder in &mut self.server_holders {
+                let server_info = server_holder.info;
+                if server_info.is_valid() {
+                    result_return_if!(handles_index >= MAX_COUNT, rc::ResultWaitHandlesFull);
+                    self.wait_handles[handles_index] = server_info.handle;
+                    handles_index += 1;
+                }
+            }
+            self.wait_handle_count = handles_index;
+            self.wait_handles_dirty = false;
         }
 
-        unsafe { core::slice::from_raw_parts(self.wait_handles.as_ptr(), handles_index) }
+        Ok(unsafe { core::slice::from_raw_parts(self.wait_handles.as_ptr(), self.wait_handle_count) })
     }
 
     #[inline(always)]
-    fn handle_request_command(&mut self, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType, domain_command_type: cmif::DomainCommandType, ipc_buf_backup: &[u8], domain_table: Option<mem::Shared<DomainTable>>) -> Result<()> {
+    fn handle_request_command(&mut self, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType, domain_command_type: cmif::DomainCommandType, ipc_buf_backup: &[u8], domain_table: Option<mem::Shared<DomainTable>>, pointer_buffer_size: usize) -> Result<bool> {
+        if (pointer_buffer_size == 0) && ctx.uses_pointer_buffer() {
+            // We don't provide any receive static for the kernel to copy pointer data to, so commands using them can't be handled
+            cmif::server::write_request_command_response_on_msg_buffer(ctx, rc::ResultPointerBufferUnavailable::make(), command_type);
+            return Ok(false);
+        }
+
         let is_domain = ctx.object_info.is_domain();
         let domain_table_clone = domain_table.clone();
-        let do_handle_request = || -> Result<()> {
+        let pointer_buffer = self.pointer_buffer.as_mut_ptr();
+        let forwarded_copy_handles = &mut self.forwarded_copy_handles;
+        // Returns whether the (mitm) forward session was closed, in which case our session must be closed too
+        // The outer result (whether the session should be closed) also accounts for base domain objects being closed
+        let do_handle_request = || -> Result<bool> {
+            let mut forward_session_closed = false;
+            // Empty vectors don't allocate, thus the common case (commands not creating sessions) has no heap churn, and only commands creating sessions allocate
             let mut new_sessions: Vec<ServerHolder> = Vec::new();
             for server_holder in &mut self.server_holders {
                 let server_info = server_holder.info;
@@ -490,87 +1116,119 @@ impl<const P: usize> ServerManager<P> {
                         unsafe {
                             core::ptr::copy(ipc_buf_backup.as_ptr(), ipc_buf, ipc_buf_backup.len());
                         }
+                        if pointer_buffer_size > 0 {
+                            cmif::server::redirect_receive_statics_on_msg_buffer(pointer_buffer, pointer_buffer_size);
+                        }
                         // Let the original service take care of the command for us.
                         svc::send_sync_request(server_holder.mitm_forward_info.handle)
                     };
                     
                     let target_server = match is_domain {
                         true => match ctx.object_info.owns_handle {
-                            true => server_holder.server.clone().ok_or(rc::ResultSignaledServerNotFound::make())?,
-                            false => domain_table.ok_or(rc::ResultDomainNotFound::make())?.get().find_domain(ctx.object_info.domain_object_id)?
+                            true => server_holder.get_server().cloned().ok_or(rc::ResultSignaledServerNotFound::make())?,
+                            // The object ID comes from the client, thus an unknown one is answered with an error instead of failing the whole processing
+                            false => match domain_table.ok_or(rc::ResultDomainNotFound::make()).and_then(|domain_table| domain_table.get().find_domain(ctx.object_info.domain_object_id)) {
+                                Ok(domain_server) => domain_server,
+                                Err(rc) => {
+                                    cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
+                                    break;
+                                }
+                            }
                         },
-                        false => server_holder.server.clone().ok_or(rc::ResultSignaledServerNotFound::make())?
+                        false => server_holder.get_server().cloned().ok_or(rc::ResultSignaledServerNotFound::make())?
                     };
                     // Nothing done on success here, as if the command succeeds it will automatically respond by itself.
-                    let mut command_found = false;
+                    let protocol = ctx.object_info.protocol;
                     let command_table = target_server.get().get_command_metadata_table();
-                    for command in &command_table {
-                        if command.matches(rq_id) {
-                            command_found = true;
-                            let protocol = ctx.object_info.protocol;
-                            let mut server_ctx = ServerContext::new(ctx, DataWalker::empty(), domain_table_clone.clone(), &mut new_sessions);
-                            if let Err(rc) = target_server.get().call_self_server_command(command.command_fn, protocol, &mut server_ctx) {
-                                if server_holder.is_mitm_service && sm::mitm::rc::ResultShouldForwardToSession::matches(rc) {
-                                    if let Err(rc) = send_to_forward_handle() {
+                    let dispatch_rc = {
+                        let mut server_ctx = ServerContext::new(ctx, DataWalker::empty(), domain_table_clone.clone(), &mut new_sessions);
+                        server_ctx.mitm_process_info = server_holder.mitm_process_info;
+                        server_ctx.user_data = server_holder.user_data.clone();
+                        match command_table.iter().find(|command| command.matches_protocol(rq_id, protocol)) {
+                            Some(command) => target_server.get().call_self_server_command(command.command_fn, protocol, &mut server_ctx),
+                            None => match server_holder.is_mitm_service {
+                                // Unknown commands are always left to the original service
+                                true => sm::mitm::rc::ResultShouldForwardToSession::make_err(),
+                                false => target_server.get().on_unknown_command(rq_id, &mut server_ctx)
+                            }
+                        }
+                    };
+
+                    // Any dispatch stage may request forwarding, so this is handled in a single place
+                    if let Err(rc) = dispatch_rc {
+                        if server_holder.is_mitm_service && sm::mitm::rc::ResultShouldForwardToSession::matches(rc) {
+                            // Only transport failures (the request couldn't be forwarded at all) are written as our response
+                            // Otherwise the message buffer holds the original service's response, which is sent back untouched even if it contains an error result
+                            match send_to_forward_handle() {
+                                Ok(()) => {
+                                    // The response is relayed as-is, thus the kernel keeps the move/copy classification of its handles when replying: move handles are moved from us to the client, but copy handles are copied, leaving our copies behind
+                                    // Those are closed once the reply is sent (see process_signaled_handle)
+                                    let mut forward_ctx = CommandContext::new_client(server_holder.mitm_forward_info);
+                                    cmif::client::read_command_response_from_msg_buffer(&mut forward_ctx);
+                                    forwarded_copy_handles.extend_from_slice(&forward_ctx.out_params.copy_handles);
+                                },
+                                Err(rc) => {
+                                    if svc::rc::ResultSessionClosed::matches(rc) {
+                                        forward_session_closed = true;
+                                    }
+                                    else {
                                         cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
                                     }
                                 }
-                                else {
-                                    cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
-                                }
-                            }
-                        }
-                    }
-                    if !command_found {
-                        if server_holder.is_mitm_service {
-                            if let Err(rc) = send_to_forward_handle() {
-                                cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
                             }
                         }
                         else {
-                            cmif::server::write_request_command_response_on_msg_buffer(ctx, cmif::rc::ResultInvalidCommandRequestId::make(), command_type);
+                            cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
                         }
                     }
                     break;
                 }
             }
 
-            self.server_holders.append(&mut new_sessions);
+            if !new_sessions.is_empty() {
+                self.server_holders.append(&mut new_sessions);
+                self.wait_handles_dirty = true;
+            }
 
-            Ok(())
+            Ok(forward_session_closed)
         };
 
-        match domain_command_type {
-            cmif::DomainCommandType::Invalid => {
-                // Invalid command type might mean that the session isn't a domain :P
-                match is_domain {
-                    false => do_handle_request()?,
-                    true => return rc::ResultInvalidDomainCommandType::make_err()
-                };
-            },
-            cmif::DomainCommandType::SendMessage => do_handle_request()?,
-            cmif::DomainCommandType::Close => {
+        // Malformed domain commands come from the client, thus they are answered with an error result (instead of being ignored or failing the whole processing)
+        let mut should_close_session = false;
+        match (domain_command_type, is_domain) {
+            // Invalid command type means that the session isn't a domain :P
+            (cmif::DomainCommandType::Invalid, false) => should_close_session = do_handle_request()?,
+            (cmif::DomainCommandType::Invalid, true) => cmif::server::write_request_command_response_on_msg_buffer(ctx, rc::ResultInvalidDomainCommandType::make(), command_type),
+            // The domain header is only read for domain sessions, but don't rely on that
+            (_, false) => cmif::server::write_request_command_response_on_msg_buffer(ctx, rc::ResultNotDomain::make(), command_type),
+            (cmif::DomainCommandType::SendMessage, true) => should_close_session = do_handle_request()?,
+            (cmif::DomainCommandType::Close, true) => {
                 if !ctx.object_info.owns_handle {
-                    domain_table_clone.ok_or(rc::ResultDomainNotFound::make())?.get().deallocate_domain(ctx.object_info.domain_object_id);
+                    let close_rc = domain_table_clone.ok_or(rc::ResultDomainNotFound::make()).and_then(|domain_table| domain_table.get().deallocate_domain(ctx.object_info.domain_object_id));
+                    if let Err(rc) = close_rc {
+                        cmif::server::write_request_command_response_on_msg_buffer(ctx, rc, command_type);
+                    }
                 }
                 else {
-                    // TODO: Abort? Error?
+                    // The base domain object itself is being closed, so close the session, which tears down the whole domain (all subobjects) unless a cloned session still uses it (see ServerHolder::run_close_hooks)
+                    should_close_session = true;
                 }
             }
         }
 
-        Ok(())
+        Ok(should_close_session)
     }
 
     #[inline(always)]
-    fn handle_control_command(&mut self, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType) -> Result<()> {
+    fn handle_control_command(&mut self, ctx: &mut CommandContext, rq_id: u32, command_type: cmif::CommandType, pointer_buffer_size: usize) -> Result<()> {
         // Control commands only exist in CMIF...
         result_return_unless!(ctx.object_info.uses_cmif_protocol(), super::rc::ResultInvalidProtocol);
 
+        let max_cloned_object_count = self.max_cloned_object_count;
         for server_holder in &mut self.server_holders {
             let server_info = server_holder.info;
             if server_info.handle == ctx.object_info.handle {
-                let mut hipc_manager = HipcManager::new(server_holder, P);
+                let mut hipc_manager = HipcManager::new(server_holder, pointer_buffer_size, max_cloned_object_count);
                 // Nothing done on success here, as if the command succeeds it will automatically respond by itself.
                 let mut command_found = false;
                 let command_table = hipc_manager.get_command_metadata_table();
@@ -592,6 +1250,7 @@ impl<const P: usize> ServerManager<P> {
                 if hipc_manager.has_cloned_object() {
                     let cloned_holder = hipc_manager.clone_object()?;
                     self.server_holders.push(cloned_holder);
+                    self.wait_handles_dirty = true;
                 }
                 break;
             }
@@ -600,11 +1259,21 @@ impl<const P: usize> ServerManager<P> {
         Ok(())
     }
 
-    fn process_signaled_handle(&mut self, handle: svc::Handle) -> Result<()> {
+    fn process_signaled_handle(&mut self, handle: svc::Handle) -> Result<ProcessEvent> {
+        if self.is_interrupt_handle(handle) {
+            svc::reset_signal(handle)?;
+            return Ok(ProcessEvent::Interrupted);
+        }
+
         let mut server_found = false;
         let mut index: usize = 0;
         let mut should_close_session = false;
+        // Same as in handle_request_command, this only allocates when sessions are accepted
         let mut new_sessions: Vec<ServerHolder> = Vec::new();
+        #[cfg(feature = "services")]
+        let sm_retry_policy = self.sm_retry_policy;
+        let accept_error_fn = self.accept_error_fn;
+        let accept_backlog = self.accept_backlog;
 
         let mut ctx = CommandContext::empty();
         let mut command_type = cmif::CommandType::Invalid;
@@ -612,6 +1281,7 @@ impl<const P: usize> ServerManager<P> {
         let mut rq_id: u32 = 0;
         let mut ipc_buf_backup: [u8; 0x100] = [0; 0x100];
         let mut domain_table: Option<mem::Shared<DomainTable>> = None;
+        let mut pointer_buffer_size: usize = 0;
 
         for server_holder in &mut self.server_holders {
             let server_info = server_holder.info;
@@ -619,14 +1289,22 @@ impl<const P: usize> ServerManager<P> {
                 server_found = true;
                 match server_holder.handle_type {
                     WaitHandleType::Session => {
-                        if P > 0 {
+                        pointer_buffer_size = server_holder.get_pointer_buffer_size(P);
+                        if pointer_buffer_size > 0 {
                             // Send our pointer buffer as a C descriptor for kernel - why are Pointer buffers so fucking weird?
+                            // A single C descriptor is enough for commands with several in-pointer buffers: the kernel copies all the send statics (X descriptors) one after another into it, which is why the required pointer buffer size of each command is the sum of its buffer sizes
+                            // TODO: one receive static per region (separate pointer buffers) would only be needed for clients indexing C descriptors explicitly, which official clients don't do
                             let mut tmp_ctx = CommandContext::new_client(server_info);
-                            tmp_ctx.add_receive_static(ReceiveStaticDescriptor::new(self.pointer_buffer.as_ptr(), P))?;
+                            tmp_ctx.add_receive_static(ReceiveStaticDescriptor::new(self.pointer_buffer.as_ptr(), pointer_buffer_size))?;
                             cmif::client::write_command_on_msg_buffer(&mut tmp_ctx, cmif::CommandType::Invalid, 0);
                         }
 
-                        match svc::reply_and_receive(&handle, 1, 0, -1) {
+                        // Receive-only: there's no reply target (replies are sent separately after handling, see reply_impl below) and this session's handle is the only one received on, thus a received message can only be for it
+                        // The client closing the session is reported as ResultSessionClosed, and any index other than 0 would mean the message isn't for this session (which must never be attributed to it)
+                        match svc::reply_and_receive(&handle, 1, svc::INVALID_HANDLE, -1) {
+                            Ok(received_index) => {
+                                result_return_unless!(received_index == 0, rc::ResultUnexpectedReceivedHandle);
+                            },
                             Err(rc) => {
                                 if svc::rc::ResultSessionClosed::matches(rc) {
                                     should_close_session = true;
@@ -635,10 +1313,10 @@ impl<const P: usize> ServerManager<P> {
                                 else {
                                     return Err(rc);
                                 }
-                            },
-                            _ => {}
+                            }
                         };
 
+                        server_holder.last_activity_tick = arm::get_system_tick();
                         unsafe { core::ptr::copy(get_msg_buffer(), ipc_buf_backup.as_mut_ptr(), ipc_buf_backup.len()) };
 
                         ctx = CommandContext::new_server(server_info, self.pointer_buffer.as_mut_ptr());
@@ -676,19 +1354,50 @@ impl<const P: usize> ServerManager<P> {
                         }
                     },
                     WaitHandleType::Server => {
-                        let new_handle = svc::accept_session(handle)?;
-
-                        if server_holder.is_mitm_service {
-                            #[cfg(feature = "services")]
-                            {
-                                let sm = service::new_named_port_object::<sm::UserInterface>()?;
-                                let (info, session_handle) = sm.get().atmosphere_acknowledge_mitm_session(server_holder.service_name)?;
-                                new_sessions.push(server_holder.make_new_mitm_session(new_handle, session_handle.handle, info)?);
-                                sm.get().detach_client(sf::ProcessId::new())?;
+                        for accept_index in 0..accept_backlog {
+                            // Further sessions are only accepted if there are pending connections (the port is still signaled), so that accepting never blocks
+                            if accept_index > 0 {
+                                if svc::wait_synchronization(&handle, 1, 0).is_err() {
+                                    break;
+                                }
+                            }
+
+                            let new_handle = match svc::accept_session(handle) {
+                                Ok(new_handle) => new_handle,
+                                Err(rc) => {
+                                    // An invalid handle means our server holder is broken, anything else (like running out of resources) is likely transient
+                                    if svc::rc::ResultInvalidHandle::matches(rc) {
+                                        return Err(rc);
+                                    }
+
+                                    if let Some(accept_error_fn) = accept_error_fn {
+                                        (accept_error_fn)(handle, rc);
+                                    }
+                                    break;
+                                }
+                            };
+
+                            if server_holder.is_mitm_service {
+                                #[cfg(feature = "services")]
+                                {
+                                    let service_name = server_holder.kind.get_service_name();
+                                    let (info, session_handle) = sm_retry_policy.run(|| service::with_sm_session(|sm| sm.atmosphere_acknowledge_mitm_session(service_name)))?;
+                                    new_sessions.push(server_holder.make_new_mitm_session(new_handle, session_handle.handle, info)?);
+                                }
+                            }
+                            else {
+                                let pooled_object_index = match server_holder.new_server_fn {
+                                    Some(new_server_fn) => self.object_pool.iter().position(|pooled_object| pooled_object.is_from(new_server_fn)),
+                                    None => None
+                                };
+                                match pooled_object_index {
+                                    Some(pooled_object_index) => {
+                                        let pooled_object = self.object_pool.swap_remove(pooled_object_index);
+                                        new_sessions.push(server_holder.make_new_session_from_object(new_handle, pooled_object.object));
+                                    },
+                                    None => new_sessions.push(server_holder.make_new_session(new_handle)?)
+                                };
                             }
-                        }
-                        else {
-                            new_sessions.push(server_holder.make_new_session(new_handle)?);
                         }
                     }
                 };
@@ -697,6 +1406,9 @@ impl<const P: usize> ServerManager<P> {
             index += 1;
         }
 
+        // Reply-only in practice (zero receive handles), the zero timeout makes the kernel return TimedOut right after replying, and a reply target closed meanwhile is reported as SessionClosed (the session gets closed on the next receive)
+        // Note that this is already the cheapest reply shape: the kernel doesn't wait/poll on anything, and the next process() does the actual wait on all handles
+        // Receiving here too (non-zero handle count) must not be done as-is, since a request received along with the reply would land on the message buffer and be lost, unless it was handled right away (actual pipelining, which would need the received index/message to be carried over to the next process())
         let reply_impl = || -> Result<()> {
             match svc::reply_and_receive(&handle, 0, handle, 0) {
                 Err(rc) => {
@@ -711,17 +1423,29 @@ impl<const P: usize> ServerManager<P> {
             }
         };
 
+        #[cfg(feature = "watchdog")]
+        let _watchdog_guard = match command_type {
+            cmif::CommandType::Request | cmif::CommandType::RequestWithContext | cmif::CommandType::Control | cmif::CommandType::ControlWithContext => self.watchdog.clone().map(|watchdog| watchdog::CommandWatchdogGuard::new(watchdog, handle, rq_id)),
+            _ => None
+        };
+
         match command_type {
             cmif::CommandType::Request | cmif::CommandType::RequestWithContext => {
-                self.handle_request_command(&mut ctx, rq_id, command_type, domain_cmd_type, &ipc_buf_backup, domain_table)?;
-                reply_impl()?;
+                if self.handle_request_command(&mut ctx, rq_id, command_type, domain_cmd_type, &ipc_buf_backup, domain_table, pointer_buffer_size)? {
+                    // Either the original service closed the forward session (it might have been restarted) or the base domain object was closed, so tear down our session too
+                    should_close_session = true;
+                }
+                else {
+                    reply_impl()?;
+                }
             },
             cmif::CommandType::Control | cmif::CommandType::ControlWithContext => {
-                self.handle_control_command(&mut ctx, rq_id, command_type)?;
+                self.handle_control_command(&mut ctx, rq_id, command_type, pointer_buffer_size)?;
                 reply_impl()?;
             },
             cmif::CommandType::Close => {
                 cmif::server::write_close_command_response_on_msg_buffer(&mut ctx);
+                // reply_impl is already reply-only (zero receive handles), thus the closing session isn't re-armed
                 reply_impl()?;
             }
             _ => {
@@ -730,72 +1454,232 @@ impl<const P: usize> ServerManager<P> {
             }
         };
 
+        for forwarded_copy_handle in self.forwarded_copy_handles.drain(..) {
+            let _ = svc::close_handle(forwarded_copy_handle);
+        }
+
+        if self.zero_buffers_after_command {
+            if let cmif::CommandType::Request | cmif::CommandType::RequestWithContext | cmif::CommandType::Control | cmif::CommandType::ControlWithContext = command_type {
+                self.pointer_buffer.fill(0);
+                unsafe { core::ptr::write_bytes(get_msg_buffer(), 0, MSG_BUFFER_SIZE) };
+                // The backup isn't read afterwards, thus volatile writes so that the zeroing isn't optimized away
+                for backup_byte in ipc_buf_backup.iter_mut() {
+                    unsafe { core::ptr::write_volatile(backup_byte, 0) };
+                }
+            }
+        }
+
+        let event = match (should_close_session, new_sessions.len(), command_type) {
+            (true, _, _) => ProcessEvent::SessionClosed { handle },
+            (false, new_session_count, _) if new_session_count > 0 => ProcessEvent::NewSession { count: new_session_count },
+            (false, _, cmif::CommandType::Request | cmif::CommandType::RequestWithContext) => ProcessEvent::Request { handle, rq_id },
+            (false, _, cmif::CommandType::Control | cmif::CommandType::ControlWithContext) => ProcessEvent::ControlCommand { handle, rq_id },
+            _ => ProcessEvent::Nothing
+        };
+
         if should_close_session {
-            self.server_holders.remove(index);
+            let closed_holder = self.server_holders.remove(index);
+            closed_holder.run_close_hooks();
+            self.recycle_session_object(&closed_holder);
+            self.wait_handles_dirty = true;
         }
 
-        self.server_holders.append(&mut new_sessions);
+        if !new_sessions.is_empty() {
+            if let Some(session_user_data_fn) = self.session_user_data_fn {
+                for new_session in new_sessions.iter_mut() {
+                    new_session.user_data = (session_user_data_fn)(new_session);
+                }
+            }
+            if let Some(session_pointer_buffer_size_fn) = self.session_pointer_buffer_size_fn {
+                for new_session in new_sessions.iter_mut() {
+                    new_session.pointer_buffer_size = (session_pointer_buffer_size_fn)(new_session);
+                }
+            }
+
+            self.server_holders.append(&mut new_sessions);
+            self.wait_handles_dirty = true;
+        }
 
         match server_found {
-            true => Ok(()),
+            true => Ok(event),
             false => rc::ResultSignaledServerNotFound::make_err()
         }
     }
     
-    pub fn register_server<S: IServerObject + 'static>(&mut self, handle: svc::Handle, service_name: sm::ServiceName) {
-        self.server_holders.push(ServerHolder::new_server::<S>(handle, service_name));
+    pub fn register_server<S: IServerObject + 'static>(&mut self, handle: svc::Handle, service_name: sm::ServiceName) -> Result<()> {
+        self.push_server_holder(ServerHolder::new_server::<S>(handle, service_name))
+    }
+
+    // Registers a server object on an already registered service handle (for instance, one passed by a loader), without talking to sm
+    pub fn adopt_service_server<S: IServerObject + 'static>(&mut self, handle: svc::Handle, service_name: sm::ServiceName) -> Result<()> {
+        self.push_server_holder(ServerHolder::new_adopted_server::<S>(handle, service_name))
     }
 
-    pub fn register_mitm_server<S: IMitmServerObject + 'static>(&mut self, handle: svc::Handle, service_name: sm::ServiceName) {
-        self.server_holders.push(ServerHolder::new_mitm_server::<S>(handle, service_name));
+    pub fn register_mitm_server<S: IMitmServerObject + 'static>(&mut self, handle: svc::Handle, service_name: sm::ServiceName) -> Result<()> {
+        self.push_server_holder(ServerHolder::new_mitm_server::<S>(handle, service_name))
     }
     
-    pub fn register_session<S: ISessionObject + 'static>(&mut self, handle: svc::Handle, session_obj: mem::Shared<S>) {
-        self.server_holders.push(ServerHolder::new_session(handle, session_obj));
+    pub fn register_session<S: ISessionObject + 'static>(&mut self, handle: svc::Handle, session_obj: mem::Shared<S>) -> Result<()> {
+        self.push_server_holder(ServerHolder::new_session(handle, session_obj))
     }
     
     #[cfg(feature = "services")]
     pub fn register_service_server<S: IService + 'static>(&mut self) -> Result<()> {
+        Self::check_pointer_buffer_size(S::required_pointer_buffer_size())?;
         let service_name = S::get_name();
         
-        let sm = service::new_named_port_object::<sm::UserInterface>()?;
-        let service_handle = sm.get().register_service(service_name, false, S::get_max_sesssions())?;
-        self.register_server::<S>(service_handle.handle, service_name);
-        sm.get().detach_client(sf::ProcessId::new())?;
-        Ok(())
+        let service_handle = match self.sm_retry_policy.run(|| service::with_sm_session(|sm| sm.register_service(service_name, false, S::get_max_sesssions()))) {
+            Ok(service_handle) => service_handle,
+            Err(rc) => {
+                if sm::rc::ResultAlreadyRegistered::matches(rc) {
+                    return rc::ResultServiceAlreadyRegistered::make_err();
+                }
+                return Err(rc);
+            }
+        };
+        self.register_server::<S>(service_handle.handle, service_name)
     }
     
     #[cfg(feature = "services")]
     pub fn register_mitm_service_server<S: IMitmService + 'static>(&mut self) -> Result<()> {
+        Self::check_pointer_buffer_size(S::required_pointer_buffer_size())?;
+        // Both the mitm server and the query session need a wait slot, check it before installing the mitm so that it isn't left half-registered
+        result_return_if!((self.get_wait_handle_count() + 2) > MAX_COUNT, rc::ResultWaitHandlesFull);
         let service_name = S::get_name();
 
-        let sm = service::new_named_port_object::<sm::UserInterface>()?;
-        let (mitm_handle, query_handle) = sm.get().atmosphere_install_mitm(service_name)?;
+        let (mitm_handle, query_handle) = self.sm_retry_policy.run(|| service::with_sm_session(|sm| sm.atmosphere_install_mitm(service_name)))?;
 
-        self.register_mitm_server::<S>(mitm_handle.handle, service_name);
+        self.register_mitm_server::<S>(mitm_handle.handle, service_name)?;
 
         let mitm_query_srv = mem::Shared::new(MitmQueryService::<S>::new());
-        self.register_session(query_handle.handle, mitm_query_srv);
+        self.register_session(query_handle.handle, mitm_query_srv)?;
 
-        sm.get().atmosphere_clear_future_mitm(service_name)?;
-        sm.get().detach_client(sf::ProcessId::new())?;
+        service::with_sm_session(|sm| sm.atmosphere_clear_future_mitm(service_name))?;
         Ok(())
     }
 
     pub fn register_named_port_server<S: INamedPort + 'static>(&mut self) -> Result<()> {
-        let port_handle = svc::manage_named_port(S::get_port_name().as_ptr(), S::get_max_sesssions())?;
+        Self::check_pointer_buffer_size(S::required_pointer_buffer_size())?;
+        // The kernel fails with InvalidState if the port name is already managed (most likely another instance of this server is running)
+        // Nothing gets registered on failure, so no partial holder is left behind
+        let port_handle = svc::manage_named_port(S::get_port_name().as_ptr(), S::get_max_sesssions()).map_err(|rc| {
+            if svc::rc::ResultInvalidState::matches(rc) {
+                rc::ResultPortAlreadyManaged::make()
+            }
+            else {
+                rc
+            }
+        })?;
 
-        self.register_server::<S>(port_handle, sm::ServiceName::empty());
-        Ok(())
+        self.push_server_holder(ServerHolder::new_named_port_server::<S>(port_handle))
+    }
+
+    fn find_signaled_info(&self, handle: svc::Handle) -> Result<SignaledInfo> {
+        for server_holder in &self.server_holders {
+            if server_holder.info.handle == handle {
+                return Ok(SignaledInfo { handle, service_name: server_holder.kind.get_service_name(), handle_type: server_holder.handle_type });
+            }
+        }
+
+        rc::ResultSignaledServerNotFound::make_err()
     }
 
-    pub fn process(&mut self) -> Result<()> {
-        let handles = self.prepare_wait_handles();
+    // Same as process() but waiting with the given timeout, and also returning which holder got signaled (meant for profiling/instrumentation)
+    // Note that the info is gathered before processing, since the signaled holder might be removed while processing it (a closed session, for instance)
+    // There's no holder behind the interrupt event, thus no info is returned for it (it's reported as ProcessEvent::Interrupted, like process() does)
+    pub fn process_with_info(&mut self, timeout: i64) -> Result<(Option<SignaledInfo>, ProcessEvent)> {
+        self.close_idle_sessions();
+        self.reclaim_zero_handle_holders();
+        let handles = self.prepare_wait_handles()?;
+        // A session-only manager might have no holders left, and waiting on an empty set would block forever
+        result_return_if!(handles.is_empty(), rc::ResultNoHandlesToWait);
+        let index = wait::wait_handles(handles, timeout)?;
+
+        let signaled_handle = self.wait_handles[index];
+        let signaled_info = match self.is_interrupt_handle(signaled_handle) {
+            true => None,
+            false => Some(self.find_signaled_info(signaled_handle)?)
+        };
+        let event = self.process_signaled_handle(signaled_handle)?;
+
+        Ok((signaled_info, event))
+    }
+
+    pub fn process(&mut self) -> Result<ProcessEvent> {
+        self.close_idle_sessions();
+        self.reclaim_zero_handle_holders();
+        let handles = self.prepare_wait_handles()?;
+        // A session-only manager might have no holders left, and waiting on an empty set would block forever
+        result_return_if!(handles.is_empty(), rc::ResultNoHandlesToWait);
         let index = wait::wait_handles(handles, -1)?;
 
         let signaled_handle = self.wait_handles[index];
-        self.process_signaled_handle(signaled_handle)?;
+        self.process_signaled_handle(signaled_handle)
+    }
+
+    // Same as process() but, after the first signaled handle is processed, also processes the ones which are already signaled (polling with zero timeout)
+    // The wait handles are re-prepared on every iteration, since processing might close sessions or accept new ones
+    // The draining is bounded to the amount of handles there were, so that continuous load can't starve the caller
+    pub fn process_all_signaled(&mut self) -> Result<()> {
+        self.process()?;
+
+        let max_drain_count = self.prepare_wait_handles()?.len();
+        for _ in 0..max_drain_count {
+            self.reclaim_zero_handle_holders();
+            let handles = self.prepare_wait_handles()?;
+            if handles.is_empty() {
+                break;
+            }
+
+            match svc::wait_synchronization(handles.as_ptr(), handles.len() as u32, 0) {
+                Ok(index) => {
+                    let signaled_handle = self.wait_handles[index as usize];
+                    self.process_signaled_handle(signaled_handle)?;
+                },
+                Err(rc) => {
+                    if svc::rc::ResultTimedOut::matches(rc) {
+                        break;
+                    }
+                    return Err(rc);
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    // Orderly shutdown: stop accepting new sessions (dropping server holders unregisters them), process any already signaled requests and finally close the remaining sessions
+    // Like in process_all_signaled, the draining is bounded to the amount of handles there were, so that clients continuously sending requests can't block the shutdown
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.server_holders.retain(|server_holder| server_holder.handle_type != WaitHandleType::Server);
+        self.wait_handles_dirty = true;
+
+        let max_drain_count = self.prepare_wait_handles()?.len();
+        for _ in 0..max_drain_count {
+            self.reclaim_zero_handle_holders();
+            let handles = self.prepare_wait_handles()?;
+            if handles.is_empty() {
+                break;
+            }
+
+            // Just poll (zero timeout) for requests which are already pending
+            match svc::wait_synchronization(handles.as_ptr(), handles.len() as u32, 0) {
+                Ok(index) => {
+                    let signaled_handle = self.wait_handles[index as usize];
+                    self.process_signaled_handle(signaled_handle)?;
+                },
+                Err(rc) => {
+                    if svc::rc::ResultTimedOut::matches(rc) {
+                        break;
+                    }
+                    return Err(rc);
+                }
+            };
+        }
 
+        self.server_holders.clear();
+        self.object_pool.clear();
+        self.wait_handles_dirty = true;
         Ok(())
     }
 
@@ -8,5 +8,18 @@ result_define_subgroup!(rc::RESULT_MODULE, RESULT_SUBMODULE => {
     InvalidCommandType: 3,
     InvalidDomainCommandType: 4,
     SignaledServerNotFound: 5,
-    AlreadyDomain: 6
+    AlreadyDomain: 6,
+    ServiceAlreadyRegistered: 7,
+    PointerBufferTooSmall: 8,
+    ObjectAlreadyRegistered: 9,
+    ObjectNotRegistered: 10,
+    DomainConversionNotAllowed: 11,
+    PointerBufferUnavailable: 12,
+    NoHandlesToWait: 13,
+    TooManyClonedObjects: 14,
+    PortAlreadyManaged: 15,
+    InterruptNotEnabled: 16,
+    WaitHandlesFull: 17,
+    UnexpectedReceivedHandle: 18,
+    NotDomain: 19
 });
\ No newline at end of file
@@ -0,0 +1,124 @@
+use crate::result::*;
+use crate::svc;
+use crate::arm;
+use crate::mem;
+use crate::thread;
+use crate::wait;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+// Called (from the watchdog thread) with the session handle, the request ID and the elapsed time (in nanoseconds) of a command which exceeded the budget
+pub type WatchdogReportFn = fn(svc::Handle, u32, u64);
+
+const THREAD_STACK_SIZE: usize = 0x2000;
+
+// Since no_std commands can't really be interrupted, the watchdog only detects (and reports, once per command) commands running past the budget
+pub struct CommandWatchdog {
+    handle: AtomicU32,
+    rq_id: AtomicU32,
+    // Zero means no command is currently running
+    start_tick: AtomicU64,
+    reported: AtomicBool,
+    should_exit: AtomicBool,
+    // Signaled when the watchdog is dropped, so that the thread exits right away instead of finishing its current wait
+    exit_event: wait::SystemEvent,
+    budget_ns: u64,
+    report_fn: WatchdogReportFn,
+    self_ptr: *mut CommandWatchdog,
+    thread: Option<thread::Thread>
+}
+
+impl CommandWatchdog {
+    fn thread_entry(watchdog_ptr: &*mut CommandWatchdog) {
+        let watchdog = unsafe { &**watchdog_ptr };
+        // Check twice per budget period, so overruns are reported at most half a budget late
+        let poll_ns = core::cmp::max(watchdog.budget_ns / 2, 1) as i64;
+
+        while !watchdog.should_exit.load(Ordering::Acquire) {
+            match wait::wait_handles(&[watchdog.exit_event.client_handle], poll_ns) {
+                Err(rc) if svc::rc::ResultTimedOut::matches(rc) => watchdog.check(),
+                // Either the exit event was signaled or waiting is just broken, thus stop watching anyway
+                _ => break
+            };
+        }
+    }
+
+    fn check(&self) {
+        let start_tick = self.start_tick.load(Ordering::Acquire);
+        if start_tick == 0 {
+            return;
+        }
+
+        let elapsed_ns = arm::ticks_to_nanoseconds(arm::get_system_tick().wrapping_sub(start_tick));
+        if (elapsed_ns > self.budget_ns) && !self.reported.swap(true, Ordering::AcqRel) {
+            (self.report_fn)(self.handle.load(Ordering::Acquire), self.rq_id.load(Ordering::Acquire), elapsed_ns);
+        }
+    }
+
+    // The watchdog is allocated on the heap since its thread keeps a pointer to it
+    pub fn new(budget_ns: u64, report_fn: WatchdogReportFn) -> Result<mem::Shared<Self>> {
+        let exit_event = wait::SystemEvent::new()?;
+        let watchdog = mem::Shared::new(Self {
+            handle: AtomicU32::new(0),
+            rq_id: AtomicU32::new(0),
+            start_tick: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+            should_exit: AtomicBool::new(false),
+            exit_event,
+            budget_ns,
+            report_fn,
+            self_ptr: core::ptr::null_mut(),
+            thread: None
+        });
+
+        let watchdog_ref = watchdog.get();
+        watchdog_ref.self_ptr = watchdog_ref as *mut Self;
+        watchdog_ref.thread = Some(thread::Thread::new(Self::thread_entry, &watchdog_ref.self_ptr, "nx.ipc.server.Watchdog", THREAD_STACK_SIZE)?);
+        if let Some(thread) = watchdog_ref.thread.as_mut() {
+            thread.initialize(thread::PRIORITY_AUTO, svc::DEFAULT_PROCESS_PROCESSOR_ID)?;
+            thread.start()?;
+        }
+
+        Ok(watchdog)
+    }
+
+    pub fn begin_command(&self, handle: svc::Handle, rq_id: u32) {
+        self.handle.store(handle, Ordering::Release);
+        self.rq_id.store(rq_id, Ordering::Release);
+        self.reported.store(false, Ordering::Release);
+        // The tick is never zero in practice, but make sure it can't be confused with the idle state
+        self.start_tick.store(core::cmp::max(arm::get_system_tick(), 1), Ordering::Release);
+    }
+
+    pub fn end_command(&self) {
+        self.start_tick.store(0, Ordering::Release);
+    }
+}
+
+impl Drop for CommandWatchdog {
+    fn drop(&mut self) {
+        // The event is only closed (dropped) after the thread is joined
+        self.should_exit.store(true, Ordering::Release);
+        let _ = self.exit_event.signal();
+        if let Some(thread) = self.thread.as_mut() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Marks the command as finished once dropped, so early returns from the dispatch path don't leave it marked as running
+pub struct CommandWatchdogGuard {
+    watchdog: mem::Shared<CommandWatchdog>
+}
+
+impl CommandWatchdogGuard {
+    pub fn new(watchdog: mem::Shared<CommandWatchdog>, handle: svc::Handle, rq_id: u32) -> Self {
+        watchdog.get().begin_command(handle, rq_id);
+        Self { watchdog }
+    }
+}
+
+impl Drop for CommandWatchdogGuard {
+    fn drop(&mut self) {
+        self.watchdog.get().end_command();
+    }
+}
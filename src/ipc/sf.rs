@@ -3,12 +3,34 @@ use crate::svc;
 use crate::version;
 use core::mem;
 use core::ptr;
+use core::cell::Cell;
 use alloc::vec::Vec;
 use alloc::string::String;
 
+/// Transfer modes which can override the one specified by a buffer's [`BufferAttribute`]s (see [`Buffer::with_transfer_mode`])
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BufferTransferMode {
+    Pointer,
+    MapAlias,
+    NonSecureMapAlias,
+    NonDeviceMapAlias
+}
+
+impl BufferTransferMode {
+    pub const fn get_attributes(self) -> BufferAttribute {
+        match self {
+            Self::Pointer => BufferAttribute::Pointer(),
+            Self::MapAlias => BufferAttribute::MapAlias(),
+            Self::NonSecureMapAlias => BufferAttribute::from(BufferAttribute::MapAlias().get() | BufferAttribute::MapTransferAllowsNonSecure().get()),
+            Self::NonDeviceMapAlias => BufferAttribute::from(BufferAttribute::MapAlias().get() | BufferAttribute::MapTransferAllowsNonDevice().get())
+        }
+    }
+}
+
 pub struct Buffer<const A: BufferAttribute, T> {
     buf: *mut T,
-    count: usize
+    count: usize,
+    transfer_mode: Option<BufferTransferMode>
 }
 
 impl<const A: BufferAttribute, T> Buffer<A, T> {
@@ -19,7 +41,8 @@ impl<const A: BufferAttribute, T> Buffer<A, T> {
     pub const fn empty() -> Self {
         Self {
             buf: ptr::null_mut(),
-            count: 0
+            count: 0,
+            transfer_mode: None
         }
     }
 
@@ -28,21 +51,24 @@ impl<const A: BufferAttribute, T> Buffer<A, T> {
     pub const fn new(addr: *mut u8, size: usize) -> Self {
         Self {
             buf: addr as *mut T,
-            count: size / Self::get_expected_size()
+            count: size / Self::get_expected_size(),
+            transfer_mode: None
         }
     }
     
     pub const fn from_ptr(buf: *const T, count: usize) -> Self {
         Self {
             buf: buf as *mut T,
-            count
+            count,
+            transfer_mode: None
         }
     }
 
     pub const fn from_mut_ptr(buf: *mut T, count: usize) -> Self {
         Self {
             buf,
-            count
+            count,
+            transfer_mode: None
         }
     }
 
@@ -88,6 +114,36 @@ impl<const A: BufferAttribute, T> Buffer<A, T> {
         self.count
     }
 
+    // Useful for commands choosing between small pointer transfers and large map-alias transfers depending on the runtime size
+    pub const fn with_transfer_mode(self, transfer_mode: BufferTransferMode) -> Self {
+        Self {
+            transfer_mode: Some(transfer_mode),
+            ..self
+        }
+    }
+
+    pub const fn get_transfer_mode(&self) -> Option<BufferTransferMode> {
+        self.transfer_mode
+    }
+
+    /// Gets the type-level attributes with the transfer mode replaced by the given one (if any)
+    pub const fn make_attributes(transfer_mode: Option<BufferTransferMode>) -> BufferAttribute {
+        match transfer_mode {
+            Some(transfer_mode) => BufferAttribute::from((A.get() & (BufferAttribute::In().get() | BufferAttribute::Out().get())) | transfer_mode.get_attributes().get()),
+            None => A
+        }
+    }
+
+    /// Gets the effective attributes of the buffer (see [`Buffer::make_attributes`])
+    pub const fn get_attributes(&self) -> BufferAttribute {
+        Self::make_attributes(self.transfer_mode)
+    }
+
+    // Whether the address satisfies T's alignment (null/empty buffers are trivially aligned)
+    pub fn is_aligned(&self) -> bool {
+        (self.buf as usize) % mem::align_of::<T>() == 0
+    }
+
     pub const fn get_var(&self) -> &T {
         unsafe {
             &*(self.buf as *const T)
@@ -154,20 +210,56 @@ pub type OutPointerBuffer<T> = Buffer<{bit_group!{ BufferAttribute [Out, Pointer
 pub type InFixedPointerBuffer<T> = Buffer<{bit_group!{ BufferAttribute [In, Pointer, FixedSize] }}, T>;
 pub type OutFixedPointerBuffer<T> = Buffer<{bit_group!{ BufferAttribute [Out, Pointer, FixedSize] }}, T>;
 
-#[derive(Clone)]
 pub struct Handle<const M: HandleMode> {
-    pub handle: svc::Handle
+    pub handle: svc::Handle,
+    // Only set for move handles created via MoveHandle::take(), cleared once the handle is sent (the kernel moves it out of our handle table)
+    owns_handle: Cell<bool>
 }
 
 impl<const M: HandleMode> Handle<M> {
+    // Note that handles created this way never close the handle themselves
     pub const fn from(handle: svc::Handle) -> Self {
-        Self { handle }
+        Self { handle, owns_handle: Cell::new(false) }
+    }
+
+    pub fn mark_transferred(&self) {
+        self.owns_handle.set(false);
+    }
+}
+
+impl<const M: HandleMode> Clone for Handle<M> {
+    // Clones never own the handle, otherwise it would get closed more than once
+    fn clone(&self) -> Self {
+        Self::from(self.handle)
+    }
+}
+
+impl<const M: HandleMode> Drop for Handle<M> {
+    // Move handles which were taken but never sent are closed, instead of being leaked
+    fn drop(&mut self) {
+        if self.owns_handle.get() {
+            let _ = svc::close_handle(self.handle);
+        }
     }
 }
 
 pub type CopyHandle = Handle<{HandleMode::Copy}>;
 pub type MoveHandle = Handle<{HandleMode::Move}>;
 
+impl MoveHandle {
+    // Takes ownership of the handle: it's transferred when sent (as a command parameter or a response one) or closed if it's dropped before that
+    pub const fn take(handle: svc::Handle) -> Self {
+        Self { handle, owns_handle: Cell::new(true) }
+    }
+}
+
+impl CopyHandle {
+    // Copy handles are just borrowed: the kernel copies them, thus they remain owned (and must be closed) by whoever owned them in the first place
+    pub const fn borrow(handle: svc::Handle) -> Self {
+        Self::from(handle)
+    }
+}
+
 #[derive(Clone)]
 pub struct ProcessId {
     pub process_id: u64
@@ -222,6 +314,27 @@ impl<E: Copy + Clone, T: Copy + Clone> EnumAsPrimitiveType<E, T> {
     }
 }
 
+// Loopback sessions (see ipc::loopback) aren't kernel sessions, thus they're closed through the loopback transport instead
+#[inline(always)]
+fn send_close_request(handle: svc::Handle) -> Result<()> {
+    #[cfg(feature = "test-harness")]
+    if loopback::is_loopback_handle(handle) {
+        return loopback::send_sync_request(handle);
+    }
+
+    svc::send_sync_request(handle)
+}
+
+#[inline(always)]
+fn close_session_handle(handle: svc::Handle) -> Result<()> {
+    #[cfg(feature = "test-harness")]
+    if loopback::is_loopback_handle(handle) {
+        return loopback::close_handle(handle);
+    }
+
+    svc::close_handle(handle)
+}
+
 pub struct Session {
     pub object_info: ObjectInfo
 }
@@ -239,7 +352,11 @@ impl Session {
         Self::from(ObjectInfo::from_handle(handle))
     }
 
+    // Subobject IDs are allocated by the server: objects returned by commands on a domain session are received as domain object IDs (see CommandContext::pop_object), sharing this session's handle
     pub fn convert_to_domain(&mut self) -> Result<()> {
+        // Same result the server side returns for sessions which are already domains
+        result_return_if!(self.object_info.is_domain(), server::rc::ResultAlreadyDomain);
+
         self.object_info.domain_object_id = self.object_info.convert_current_object_to_domain()?;
         Ok(())
     }
@@ -252,12 +369,17 @@ impl Session {
         self.object_info = info;
     }
 
+    // Sets the timeout (in nanoseconds, -1 to wait indefinitely) honored by client command calls on this session, see ipc::client::send_sync_request
+    pub fn set_call_timeout(&mut self, timeout: i64) {
+        self.object_info.call_timeout = timeout;
+    }
+
     pub fn close(&mut self) {
         if self.object_info.is_valid() {
             if self.object_info.is_domain() {
                 let mut ctx = CommandContext::new_client(self.object_info);
                 cmif::client::write_request_command_on_msg_buffer(&mut ctx, None, cmif::DomainCommandType::Close);
-                let _ = svc::send_sync_request(self.object_info.handle);
+                let _ = send_close_request(self.object_info.handle);
             }
             else if self.object_info.owns_handle {
                 let mut ctx = CommandContext::new_client(self.object_info);
@@ -267,10 +389,10 @@ impl Session {
                     CommandProtocol::Tipc => tipc::client::write_close_command_on_msg_buffer(&mut ctx)
                 };
 
-                let _ = svc::send_sync_request(self.object_info.handle);
+                let _ = send_close_request(self.object_info.handle);
             }
             if self.object_info.owns_handle {
-                let _ = svc::close_handle(self.object_info.handle);
+                let _ = close_session_handle(self.object_info.handle);
             }
             self.object_info = ObjectInfo::new();
         }
@@ -291,6 +413,37 @@ pub struct CommandMetadata {
 
 pub type CommandMetadataTable = Vec<CommandMetadata>;
 
+// Used by the interface definition macros to reject duplicate request IDs at compile time
+// The same ID may be reused by commands whose version intervals don't overlap (commands replaced on a certain version)
+#[doc(hidden)]
+pub const fn request_ids_are_unique(rq_ids: &[u32], ver_intvs: &[version::VersionInterval]) -> bool {
+    let mut i = 0;
+    while i < rq_ids.len() {
+        let mut j = i + 1;
+        while j < rq_ids.len() {
+            if (rq_ids[i] == rq_ids[j]) && ver_intvs[i].overlaps(&ver_intvs[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+// Same as above, for inverted version intervals
+#[doc(hidden)]
+pub const fn version_intervals_are_valid(ver_intvs: &[version::VersionInterval]) -> bool {
+    let mut i = 0;
+    while i < ver_intvs.len() {
+        if !ver_intvs[i].is_valid() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
 impl CommandMetadata {
     pub const fn new(rq_id: u32, command_fn: server::CommandFn, ver_intv: version::VersionInterval) -> Self {
         Self {
@@ -304,6 +457,17 @@ impl CommandMetadata {
         let cur_ver = version::get_version();
         (self.rq_id == rq_id) && self.ver_intv.contains(cur_ver)
     }
+
+    // CMIF request IDs are matched as they are, while for TIPC the received ID is the raw command type, which is offset (see tipc::REQUEST_COMMAND_TYPE_BASE)
+    pub fn matches_protocol(&self, rq_id: u32, protocol: CommandProtocol) -> bool {
+        match protocol {
+            CommandProtocol::Cmif => self.matches(rq_id),
+            CommandProtocol::Tipc => match rq_id.checked_sub(tipc::REQUEST_COMMAND_TYPE_BASE) {
+                Some(tipc_rq_id) => self.matches(tipc_rq_id),
+                None => false
+            }
+        }
+    }
 }
 
 // This trait is analogous to N's nn::sf::IServiceObject type - the base trait for any kind of IPC interface
@@ -317,9 +481,14 @@ pub trait IObject {
 
     fn get_command_metadata_table(&self) -> CommandMetadataTable;
 
+    // Max pointer buffer size required by any of the object's commands (zero unless specified on the interface definition)
+    fn required_pointer_buffer_size() -> usize where Self: Sized {
+        0
+    }
+
+    // The command function must come from this object's command metadata table (see server::CommandFn)
     fn call_self_server_command(&mut self, command_fn: server::CommandFn, protocol: CommandProtocol, ctx: &mut server::ServerContext) -> Result<()> {
-        let self_fn: server::CommandSpecificFn<Self> = unsafe { core::mem::transmute(command_fn) };
-        (self_fn)(self, protocol, ctx)
+        unsafe { (command_fn)(self as *mut Self as *mut (), protocol, ctx) }
     }
 }
 
@@ -9,6 +9,9 @@ pub enum RefcountType {
     Strong
 }
 
+// Note: these follow the (older) producer interface the system's buffer queue implements, thus the values don't match the ones of current Android versions
+// There are no generation number transactions either (set/get generation number were added to Android later on)
+// TODO: support generation numbers if a transaction for them is ever found to exist
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u32)]
 pub enum ParcelTransactionId {
@@ -25,9 +28,12 @@ pub enum ParcelTransactionId {
     Disconnect = 11,
     SetSidebandStream = 12,
     AllocateBuffers = 13,
-    SetPreallocatedBuffer = 14
+    SetPreallocatedBuffer = 14,
+    GetBufferHistory = 17
 }
 
+// Note: 0xF (the buffer-queue event, signaled when a buffer gets released/becomes available) is the only native handle type the display service exposes
+// Display vsync events aren't obtained through binders, but through vi instead (see IApplicationDisplayService::get_display_vsync_event)
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(u32)]
 pub enum NativeHandleType {
@@ -14,6 +14,7 @@ ipc_sf_define_control_interface_trait! {
         clone_current_object [2, version::VersionInterval::all()]: () => (cloned_handle: sf::MoveHandle);
         query_pointer_buffer_size [3, version::VersionInterval::all()]: () => (pointer_buffer_size: u16);
         clone_current_object_ex [4, version::VersionInterval::all()]: (tag: u32) => (cloned_handle: sf::MoveHandle);
+        ping [65000, version::VersionInterval::all()]: () => ();
     }
 }
 
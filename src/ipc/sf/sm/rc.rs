@@ -1,5 +1,7 @@
 pub const RESULT_MODULE: u32 = 21;
 
 result_define_group!(RESULT_MODULE => {
-    NotInitialized: 2
+    NotInitialized: 2,
+    OutOfSessions: 3,
+    AlreadyRegistered: 4
 });
\ No newline at end of file
@@ -12,6 +12,14 @@ pub enum CommandType {
     CloseSession = 15
 }
 
+/// Represents the base command type for regular/"Request" commands (which use `16 + <request-id>` as their command type)
+pub const REQUEST_COMMAND_TYPE_BASE: u32 = 16;
+
+/// Represents the command type used for "Request" command responses
+/// 
+/// Note that clients (this crate's included) don't check it, only the result code following the headers
+pub const RESPONSE_COMMAND_TYPE: u32 = REQUEST_COMMAND_TYPE_BASE;
+
 pub mod client;
 
 pub mod server;
\ No newline at end of file
@@ -63,7 +63,7 @@ pub fn read_command_response_from_msg_buffer(ctx: &mut CommandContext) {
 #[inline(always)]
 pub fn write_request_command_on_msg_buffer(ctx: &mut CommandContext, request_id: u32) {
     // TIPC directly sends the request ID here, withot wasting data words
-    let command_type = request_id + 16;
+    let command_type = request_id + super::REQUEST_COMMAND_TYPE_BASE;
     write_command_on_msg_buffer(ctx, command_type, ctx.in_params.data_size);
 
     ctx.in_params.data_offset = ctx.in_params.data_words_offset;
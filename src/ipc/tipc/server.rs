@@ -77,23 +77,19 @@ pub fn write_command_response_on_msg_buffer(ctx: &mut CommandContext, command_ty
 
 #[inline(always)]
 pub fn read_request_command_from_msg_buffer(ctx: &mut CommandContext) -> Result<()> {
-    let ipc_buf = get_msg_buffer();
-    let data_offset = get_aligned_data_offset(ctx.in_params.data_words_offset, ipc_buf);
-
-    ctx.in_params.data_offset = data_offset;
+    // Unlike CMIF, there is no padding nor data header: the raw data directly follows the descriptors (see the client counterpart)
+    ctx.in_params.data_offset = ctx.in_params.data_words_offset;
     Ok(())
 }
 
 #[inline(always)]
 pub fn write_request_command_response_on_msg_buffer(ctx: &mut CommandContext, result: ResultCode, request_type: u32) {
     unsafe {
-        let ipc_buf = get_msg_buffer();
         let data_size = cmem::size_of::<ResultCode>() as u32 + ctx.out_params.data_size;
-        // data_size = (data_size + 1) & !1;
 
         write_command_response_on_msg_buffer(ctx, request_type, data_size);
-        let data_offset = get_aligned_data_offset(ctx.out_params.data_words_offset, ipc_buf);
-        let rc_ref = data_offset as *mut ResultCode;
+        // Same as above, the result code (followed by the raw data) directly follows the descriptors
+        let rc_ref = ctx.out_params.data_words_offset as *mut ResultCode;
         *rc_ref = result;
 
         ctx.out_params.data_offset = rc_ref.offset(1) as *mut u8;
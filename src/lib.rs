@@ -20,6 +20,8 @@
 //! 
 //! - `rand`: Enabled pseudo-RNG support, AKA the `nx::rand` module (also enables `services`)
 //! 
+//! - `test-harness`: Enables the in-process loopback IPC transport (for testing IPC interfaces/objects without actual sessions), AKA the `nx::ipc::loopback` module
+//! 
 //! Note that most of these features/modules are just simplified and easy-to-use wrappers around IPC/raw system features, so not using them doesn't fully block those features (for instance, you could use services using IPC commands more directly without the `services` feature). 
 //!
 //! # Contributing
@@ -2,4 +2,6 @@
 
 pub mod client;
 
+pub mod server;
+
 pub mod sf;
\ No newline at end of file
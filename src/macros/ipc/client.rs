@@ -71,7 +71,7 @@ macro_rules! ipc_client_send_request_command {
         walker.reset_with(ctx.in_params.data_offset);
         $( $crate::ipc::client::RequestCommandParameter::before_send_sync_request(&$in_param, &mut walker, &mut ctx)?; )*
 
-        $crate::svc::send_sync_request($obj_info.handle)?;
+        $crate::ipc::client::send_sync_request($obj_info)?;
 
         match $obj_info.protocol {
             $crate::ipc::CommandProtocol::Cmif => $crate::ipc::cmif::client::read_request_command_response_from_msg_buffer(&mut ctx)?,
@@ -104,7 +104,7 @@ macro_rules! ipc_client_send_control_command {
         walker.reset_with(ctx.in_params.data_offset);
         $( $crate::ipc::client::RequestCommandParameter::before_send_sync_request(&$in_param, &mut walker, &mut ctx)?; )*
 
-        $crate::svc::send_sync_request($obj_info.handle)?;
+        $crate::ipc::client::send_sync_request($obj_info)?;
 
         $crate::ipc::cmif::client::read_control_command_response_from_msg_buffer(&mut ctx)?;
 
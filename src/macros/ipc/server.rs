@@ -0,0 +1,69 @@
+#![macro_use]
+
+/// Macro to implement [`as_any`][`crate::ipc::server::ISessionObject::as_any`], allowing runtime downcasts of session objects
+/// 
+/// This is meant to only be used inside [`ISessionObject`][`crate::ipc::server::ISessionObject`] impls!
+/// 
+/// Note that the trait can't provide this by default, since it must be implemented on each concrete type (the concrete type is erased on `dyn ISessionObject` objects)
+/// 
+/// # Example
+/// ```
+/// use nx::ipc::server::ISessionObject;
+/// 
+/// // Let's assume a custom server object named "ExampleServer" exists
+/// impl ISessionObject for ExampleServer {
+///     ipc_server_session_object_impl_as_any!();
+/// }
+/// ```
+#[macro_export]
+macro_rules! ipc_server_session_object_impl_as_any {
+    () => {
+        fn as_any(&self) -> Option<&dyn core::any::Any> {
+            Some(self)
+        }
+    };
+}
+
+/// Simplifies the creation of a (server-side IPC) type implementing an IPC interface, for servers which don't hold any state
+/// 
+/// This defines the type and implements [`IObject`][`crate::ipc::sf::IObject`], [`ISessionObject`][`crate::ipc::server::ISessionObject`] and [`IServerObject`][`crate::ipc::server::IServerObject`] for it
+/// 
+/// Servers needing custom construction (or any state at all) must still implement these traits manually
+/// 
+/// # Examples
+/// 
+/// ```
+/// // Let's suppose a "IExampleInterface" IPC interface trait exists
+/// 
+/// // This already creates the server-IPC type and impls IObject, ISessionObject and IServerObject
+/// ipc_server_define_object_default!(ExampleServer);
+/// 
+/// impl IExampleInterface for ExampleServer {
+///     (...)
+/// }
+/// ```
+#[macro_export]
+macro_rules! ipc_server_define_object_default {
+    ($t:ident) => {
+        pub struct $t {
+            // Required, only effectively used on client IPC interfaces
+            dummy_session: $crate::ipc::sf::Session
+        }
+
+        impl $crate::ipc::sf::IObject for $t {
+            $crate::ipc_sf_object_impl_default_command_metadata!();
+
+            fn get_session(&mut self) -> &mut $crate::ipc::sf::Session {
+                &mut self.dummy_session
+            }
+        }
+
+        impl $crate::ipc::server::ISessionObject for $t {}
+
+        impl $crate::ipc::server::IServerObject for $t {
+            fn new() -> Self {
+                Self { dummy_session: $crate::ipc::sf::Session::new() }
+            }
+        }
+    };
+}
@@ -8,21 +8,36 @@
 /// use nx::version::{Version, VersionInterval};
 /// 
 /// // Define commands with their request ID, allowed version interval and in/out parameters
+/// // Optionally, the max pointer buffer size a command requires can be specified (it's zero otherwise)
 /// ipc_sf_define_interface_trait! {
 ///     trait IExampleInterface {
 ///         command_1 [1, VersionInterval::all()]: (in_32: u32) => (out_16: u16);
 ///         command_2 [20, VersionInterval::all()]: (in_8: u8) => ();
+///         command_3 [21, VersionInterval::all(), 0x200]: (out_buf: sf::OutPointerBuffer<u8>) => ();
 ///     }
 /// }
 /// 
 /// // You can impl "IExampleInterface" to create a custom object implementing the commands
 /// ```
+/// 
+/// Duplicate request IDs (on overlapping version intervals) or inverted version intervals (minimum higher than maximum) make the interface fail to compile:
+/// 
+/// ```compile_fail
+/// use nx::version::{Version, VersionInterval};
+/// 
+/// ipc_sf_define_interface_trait! {
+///     trait IBrokenInterface {
+///         command_1 [1, VersionInterval::all()]: () => ();
+///         command_2 [1, VersionInterval::all()]: () => ();
+///     }
+/// }
+/// ```
 #[macro_export]
 macro_rules! ipc_sf_define_interface_trait {
     (
         trait $intf:ident {
             $(
-                $name:ident [$rq_id:expr, $ver_intv:expr]: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty ),* )
+                $name:ident [$rq_id:expr, $ver_intv:expr $(, $buf_size:expr)?]: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty ),* )
             );* $(;)* // Note: trick to allow last trailing ';' for proper styling
         }
     ) => {
@@ -43,13 +58,15 @@ macro_rules! ipc_sf_define_interface_trait {
                         ctx.raw_data_walker = $crate::ipc::DataWalker::new(core::ptr::null_mut());
                         $( $crate::ipc::server::ResponseCommandParameter::before_response_write(&$out_param_name, &mut ctx)?; )*
                         ctx.ctx.out_params.data_size = ctx.raw_data_walker.get_offset() as u32;
+                        ctx.ctx.push_response_send_statics()?;
+                        ctx.ctx.ensure_response_fits_msg_buffer()?;
         
                         match protocol {
                             $crate::ipc::CommandProtocol::Cmif => {
                                 $crate::ipc::cmif::server::write_request_command_response_on_msg_buffer(&mut ctx.ctx, $crate::result::ResultSuccess::make(), $crate::ipc::cmif::CommandType::Request);
                             },
                             $crate::ipc::CommandProtocol::Tipc => {
-                                $crate::ipc::tipc::server::write_request_command_response_on_msg_buffer(&mut ctx.ctx, $crate::result::ResultSuccess::make(), 16); // TODO: is this command type actually read/used/relevant?
+                                $crate::ipc::tipc::server::write_request_command_response_on_msg_buffer(&mut ctx.ctx, $crate::result::ResultSuccess::make(), $crate::ipc::tipc::RESPONSE_COMMAND_TYPE);
                             }
                         };
         
@@ -58,12 +75,32 @@ macro_rules! ipc_sf_define_interface_trait {
         
                         Ok(())
                     }
+
+                    #[doc(hidden)]
+                    unsafe fn [<sf_server_shim_ $name>](this: *mut (), protocol: $crate::ipc::CommandProtocol, ctx: &mut $crate::ipc::server::ServerContext) -> $crate::result::Result<()> where Self: Sized {
+                        // The erased pointer is always the object the command table was obtained from (see call_self_server_command)
+                        let this = &mut *(this as *mut Self);
+                        this.[<sf_server_impl_ $name>](protocol, ctx)
+                    }
                 )*
 
-                fn get_sf_command_metadata_table(&self) -> $crate::ipc::sf::CommandMetadataTable {
+                fn get_sf_required_pointer_buffer_size() -> usize where Self: Sized {
+                    let mut required_size: usize = 0;
+                    $(
+                        let command_required_size: usize = 0 $( + ($buf_size) )?;
+                        required_size = core::cmp::max(required_size, command_required_size);
+                    )*
+                    required_size
+                }
+
+                fn get_sf_command_metadata_table(&self) -> $crate::ipc::sf::CommandMetadataTable where Self: Sized {
+                    // Interface definition mistakes are caught at build time instead of failing (or silently misbehaving) on command dispatch
+                    const _: () = assert!($crate::ipc::sf::request_ids_are_unique(&[ $( $rq_id ),* ], &[ $( $ver_intv ),* ]), concat!("Duplicate command request IDs in interface ", stringify!($intf)));
+                    const _: () = assert!($crate::ipc::sf::version_intervals_are_valid(&[ $( $ver_intv ),* ]), concat!("Inverted command version interval in interface ", stringify!($intf)));
+
                     vec! [
                         $(
-                            $crate::ipc::sf::CommandMetadata::new($rq_id, unsafe { core::mem::transmute(Self::[<sf_server_impl_ $name>] as fn(&mut Self, $crate::ipc::CommandProtocol, &mut $crate::ipc::server::ServerContext) -> $crate::result::Result<()>) }, $ver_intv)
+                            $crate::ipc::sf::CommandMetadata::new($rq_id, Self::[<sf_server_shim_ $name>] as $crate::ipc::server::CommandFn, $ver_intv)
                         ),*
                     ]
                 }
@@ -80,7 +117,7 @@ macro_rules! ipc_sf_define_control_interface_trait {
     (
         trait $intf:ident {
             $(
-                $name:ident [$rq_id:expr, $ver_intv:expr]: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty ),* )
+                $name:ident [$rq_id:expr, $ver_intv:expr $(, $buf_size:expr)?]: ( $( $in_param_name:ident: $in_param_type:ty ),* ) => ( $( $out_param_name:ident: $out_param_type:ty ),* )
             );* $(;)* // Same as above
         }
     ) => {
@@ -104,6 +141,8 @@ macro_rules! ipc_sf_define_control_interface_trait {
                         ctx.raw_data_walker = $crate::ipc::DataWalker::new(core::ptr::null_mut());
                         $( $crate::ipc::server::ResponseCommandParameter::before_response_write(&$out_param_name, &mut ctx)?; )*
                         ctx.ctx.out_params.data_size = ctx.raw_data_walker.get_offset() as u32;
+                        ctx.ctx.push_response_send_statics()?;
+                        ctx.ctx.ensure_response_fits_msg_buffer()?;
 
                         $crate::ipc::cmif::server::write_control_command_response_on_msg_buffer(&mut ctx.ctx, $crate::result::ResultSuccess::make(), $crate::ipc::cmif::CommandType::Control);
 
@@ -112,12 +151,32 @@ macro_rules! ipc_sf_define_control_interface_trait {
 
                         Ok(())
                     }
+
+                    #[doc(hidden)]
+                    unsafe fn [<sf_server_shim_ $name>](this: *mut (), protocol: $crate::ipc::CommandProtocol, ctx: &mut $crate::ipc::server::ServerContext) -> $crate::result::Result<()> where Self: Sized {
+                        // The erased pointer is always the object the command table was obtained from (see call_self_server_command)
+                        let this = &mut *(this as *mut Self);
+                        this.[<sf_server_impl_ $name>](protocol, ctx)
+                    }
                 )*
 
-                fn get_sf_command_metadata_table(&self) -> $crate::ipc::sf::CommandMetadataTable {
+                fn get_sf_required_pointer_buffer_size() -> usize where Self: Sized {
+                    let mut required_size: usize = 0;
+                    $(
+                        let command_required_size: usize = 0 $( + ($buf_size) )?;
+                        required_size = core::cmp::max(required_size, command_required_size);
+                    )*
+                    required_size
+                }
+
+                fn get_sf_command_metadata_table(&self) -> $crate::ipc::sf::CommandMetadataTable where Self: Sized {
+                    // Interface definition mistakes are caught at build time instead of failing (or silently misbehaving) on command dispatch
+                    const _: () = assert!($crate::ipc::sf::request_ids_are_unique(&[ $( $rq_id ),* ], &[ $( $ver_intv ),* ]), concat!("Duplicate command request IDs in interface ", stringify!($intf)));
+                    const _: () = assert!($crate::ipc::sf::version_intervals_are_valid(&[ $( $ver_intv ),* ]), concat!("Inverted command version interval in interface ", stringify!($intf)));
+
                     vec! [
                         $(
-                            $crate::ipc::sf::CommandMetadata::new($rq_id, unsafe { core::mem::transmute(Self::[<sf_server_impl_ $name>] as fn(&mut Self, $crate::ipc::CommandProtocol, &mut $crate::ipc::server::ServerContext) -> $crate::result::Result<()>) }, $ver_intv)
+                            $crate::ipc::sf::CommandMetadata::new($rq_id, Self::[<sf_server_shim_ $name>] as $crate::ipc::server::CommandFn, $ver_intv)
                         ),*
                     ]
                 }
@@ -162,6 +221,11 @@ macro_rules! ipc_sf_object_impl_default_command_metadata {
             // Provided by the interface being implemented by this object
             self.get_sf_command_metadata_table()
         }
+
+        fn required_pointer_buffer_size() -> usize where Self: Sized {
+            // Same as above
+            Self::get_sf_required_pointer_buffer_size()
+        }
     };
 }
 
@@ -11,19 +11,47 @@ use crate::util;
 
 pub mod alloc;
 
+// Sentinel value the holder gets set to after the final release (on debug builds), in order to detect double releases or usage after release
+#[cfg(debug_assertions)]
+const RELEASED_HOLDER_SENTINEL: *mut u64 = usize::MAX as *mut u64;
+
+// Reference counts stick to this value instead of wrapping around on overflow
+const SATURATED_USE_COUNT: u64 = u64::MAX;
+
+// The variable actually allocated by Shared::new, for instances pointing inside it (see Shared::map)
+#[derive(Copy, Clone)]
+struct RootObject {
+    object: *mut u8,
+    drop_fn: unsafe fn(*mut u8)
+}
+
+unsafe fn drop_root_object<T>(object: *mut u8) {
+    mem::drop(Box::from_raw(object as *mut T));
+}
+
 #[derive(Copy, Clone)]
 struct ReferenceCount {
-    holder: *mut u64
+    holder: *mut u64,
+    root: Option<RootObject>
 }
 
 impl ReferenceCount {
     #[inline]
     pub const fn new() -> Self {
-        Self { holder: ptr::null_mut() }
+        Self { holder: ptr::null_mut(), root: None }
+    }
+
+    #[inline(always)]
+    fn check_not_released(&self) {
+        #[cfg(debug_assertions)]
+        if self.holder == RELEASED_HOLDER_SENTINEL {
+            panic!("Attempted to use an already released reference count (double drop of a Shared object?)");
+        }
     }
     
     #[inline]
     pub fn use_count(&self) -> u64 {
+        self.check_not_released();
         if self.holder.is_null() {
             0
         }
@@ -33,13 +61,14 @@ impl ReferenceCount {
     }
     
     pub fn acquire<U: ?Sized>(&mut self, ptr: *mut U) {
+        self.check_not_released();
         if !ptr.is_null() {
             unsafe {
                 if self.holder.is_null() {
                     self.holder = alloc::new::<u64>().unwrap();
                     *self.holder = 1;
                 }
-                else {
+                else if *self.holder != SATURATED_USE_COUNT {
                     *self.holder += 1;
                 }
             }
@@ -47,14 +76,32 @@ impl ReferenceCount {
     }
     
     pub fn release<U: ?Sized>(&mut self, ptr: *mut U) {
+        self.check_not_released();
         if !self.holder.is_null() {
             unsafe {
+                // A saturated count no longer tracks the real amount of references, so the variable is intentionally leaked instead of risking an early free
+                if *self.holder == SATURATED_USE_COUNT {
+                    return;
+                }
+
                 *self.holder -= 1;
                 if *self.holder == 0 {
                     // We created the variable as a Box, so we destroy it the same way
-                    mem::drop(Box::from_raw(ptr));
+                    // Projected instances don't point to the boxed variable itself, thus the root one is destroyed instead
+                    match self.root {
+                        Some(root) => (root.drop_fn)(root.object),
+                        None => mem::drop(Box::from_raw(ptr))
+                    };
                     alloc::delete(self.holder);
-                    self.holder = ptr::null_mut();
+
+                    #[cfg(debug_assertions)]
+                    {
+                        self.holder = RELEASED_HOLDER_SENTINEL;
+                    }
+                    #[cfg(not(debug_assertions))]
+                    {
+                        self.holder = ptr::null_mut();
+                    }
                 }
             }
         }
@@ -80,6 +127,25 @@ impl<T> Shared<T> {
         shared.ref_count.acquire(object);
         shared
     }
+
+    /// Creates a new [`Shared`] pointing to a part of this instance's variable (typically a field), similar to `core::cell::Ref::map`
+    /// 
+    /// The new instance shares this instance's reference count, thus the whole variable is kept alive (and dropped only once) as long as any of them exists
+    /// 
+    /// # Arguments
+    /// 
+    /// * `f`: The function projecting the variable, whose returned reference must point inside the variable itself (not to anything it just borrows or owns elsewhere, which might be freed/moved while the new instance is alive)
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(&self, f: F) -> Shared<U> {
+        let object = f(self.get()) as *const U as *mut U;
+        let root = match self.ref_count.root {
+            Some(root) => root,
+            None => RootObject { object: self.object as *mut u8, drop_fn: drop_root_object::<T> }
+        };
+
+        let mut new_shared = Shared::<U> { object, ref_count: ReferenceCount { holder: self.ref_count.holder, root: Some(root) } };
+        new_shared.acquire(object);
+        new_shared
+    }
 }
 
 impl<T: ?Sized> Shared<T> {
@@ -107,12 +173,67 @@ impl<T: ?Sized> Shared<T> {
         new_shared
     }
     
+    /// Releases this [`Shared`] instance's reference to its variable, leaving the instance in a null state
+    /// 
+    /// The variable will be dropped if this was the last existing instance pointing to it
+    pub fn reset(&mut self) {
+        self.release();
+        self.ref_count = ReferenceCount::new();
+
+        // Null the data pointer, keeping any pointer metadata (vtable, length...) for unsized types
+        unsafe {
+            *(&mut self.object as *mut *mut T as *mut *mut u8) = ptr::null_mut();
+        }
+    }
+
+    /// Gets whether this [`Shared`] instance is null (see [`reset`][`Shared::reset`])
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        self.object.is_null()
+    }
+
+    #[inline]
+    fn get_object_ptr(&self) -> ptr::NonNull<T> {
+        debug_assert!(!self.is_null(), "Attempted to access the value of a null (reset) Shared object");
+        unsafe { ptr::NonNull::new_unchecked(self.object) }
+    }
+    
     /// Accesses the value inside the [`Shared`] object
     /// 
-    /// Note that the value is guaranteed to be valid since the [`Shared`] object must be created with a valid value
+    /// Note that the value is guaranteed to be valid unless the [`Shared`] object was [`reset`][`Shared::reset`], which is asserted against on debug builds
     #[inline]
     pub fn get(&self) -> &mut T {
-        unsafe { &mut *self.object }
+        unsafe { &mut *self.get_object_ptr().as_ptr() }
+    }
+
+    /// Consumes this [`Shared`] instance, returning the raw variable pointer and the raw reference count pointer (the control block allocated by [`Shared::new`])
+    ///
+    /// The reference held by this instance is intentionally leaked: the variable stays alive until the same pointers are passed to [`from_raw`][`Shared::from_raw`] and the resulting instance gets dropped
+    ///
+    /// Note that, since the reference count isn't stored alongside the variable, both pointers must be kept in order to reclaim the instance later
+    /// 
+    /// Instances obtained through [`map`][`Shared::map`] aren't supported, since they must also know their root variable in order to drop it
+    #[inline]
+    pub fn into_raw(self) -> (*mut T, *mut u64) {
+        debug_assert!(self.ref_count.root.is_none(), "Attempted to convert a projected Shared object into raw pointers");
+        let raw = (self.object, self.ref_count.holder);
+        mem::forget(self);
+        raw
+    }
+
+    /// Reclaims a [`Shared`] instance from the raw pointers previously obtained through [`into_raw`][`Shared::into_raw`]
+    ///
+    /// # Arguments
+    ///
+    /// * `object`: The raw variable pointer
+    /// * `ref_count`: The raw reference count pointer
+    ///
+    /// # Safety
+    ///
+    /// Every call must balance exactly one prior [`into_raw`][`Shared::into_raw`] call, with the exact same pointers it returned (thus the control block must be the one allocated by [`Shared::new`]), since the reclaimed instance takes over the reference the former one leaked
+    #[inline]
+    pub unsafe fn from_raw(object: *mut T, ref_count: *mut u64) -> Self {
+        Self { object, ref_count: ReferenceCount { holder: ref_count, root: None } }
     }
 
     // TODO: rename get() to get_mut() and make a get() fn returning a &T ref?
@@ -149,6 +270,10 @@ impl<T: ?Sized> Eq for Shared<T> {}
 
 /// Flushes data cache at a certain memory region
 /// 
+/// This is only needed for memory accessed by devices (like GPU buffers, which `gpu::surface::Surface` flushes before queueing them), since devices don't snoop the CPU caches
+/// 
+/// IPC buffers never need it, for any buffer kind: CPU cores are coherent with each other, the kernel copies the message buffer and pointer buffers (X/C descriptors) through coherent accesses, and map-alias buffers (A/B/W descriptors) are the client's own memory mapped on the server side. Map-alias buffers which end up being accessed by a device must be flushed by whoever hands them to it
+/// 
 /// # Arguments
 /// 
 /// * `address`: Memory region address
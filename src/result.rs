@@ -136,6 +136,18 @@ pub fn unpack<T>(rc: &Result<T>) -> ResultCode {
     }
 }
 
+/// Decodes a [`ResultCode`] into its module and description values
+/// 
+/// Note that the [`Display`][`fmt::Display`] formatting of [`ResultCode`] already prints them in the format shown by the console (`2168-0002`), this is meant for cases where they need to be checked/logged separately
+/// 
+/// # Arguments
+/// 
+/// * `rc`: The [`ResultCode`] to decode
+#[inline]
+pub const fn decode(rc: ResultCode) -> (u32, u32) {
+    (rc.get_module(), rc.get_description())
+}
+
 /// Represents a base trait for result value definitions to follow
 pub trait ResultBase {
     /// Gets the result definition's module
@@ -143,6 +143,9 @@ static mut G_MAIN_THREAD: thread::Thread = thread::Thread::empty();
 /// 
 /// This will call the HBL-specific exit fn if running as a homebrew NRO, or [`exit_process`][`svc::exit_process`] otherwise
 pub fn exit(rc: ResultCode) -> ! {
+    #[cfg(feature = "services")]
+    service::finalize_sm_session();
+
     unsafe {
         match G_EXIT_FN.get() {
             Some(exit_fn) => exit_fn(rc),
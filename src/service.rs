@@ -4,6 +4,7 @@ use crate::ipc::client;
 use crate::ipc::sf;
 use crate::mem;
 use crate::svc;
+use crate::sync;
 use crate::result::*;
 
 pub mod sm;
@@ -47,13 +48,50 @@ pub fn new_named_port_object<T: INamedPort + 'static>() -> Result<mem::Shared<T>
     Ok(mem::Shared::new(object))
 }
 
+// Recursive, since the value itself is accessed (which locks too) while the lock is held
+static mut G_SM_SESSION: sync::Locked<Option<mem::Shared<sm::UserInterface>>> = sync::Locked::new(true, None);
+
+/// Runs an operation on the process-wide `sm` session, connecting to it the first time
+/// 
+/// All `sm` operations done by this library (accessing/registering services, etc.) share this session, instead of connecting to `sm` each time
+/// 
+/// The operation runs while holding the session's lock, thus operations from different threads are serialized, and the session object itself is never handed out (its reference count isn't atomic)
+/// 
+/// # Arguments
+/// 
+/// * `f`: The operation to run on the session
+pub fn with_sm_session<T, F: FnOnce(&mut sm::UserInterface) -> Result<T>>(f: F) -> Result<T> {
+    unsafe {
+        let _lock = sync::ScopedLock::new(G_SM_SESSION.get_lock());
+        let sm_session = G_SM_SESSION.get();
+        if sm_session.is_none() {
+            *sm_session = Some(new_named_port_object::<sm::UserInterface>()?);
+        }
+
+        match sm_session.as_ref() {
+            Some(sm_session) => f(sm_session.get()),
+            None => super::rc::ResultNotInitialized::make_err()
+        }
+    }
+}
+
+/// Closes the process-wide `sm` session (see [`with_sm_session`]), if it was opened
+/// 
+/// This is automatically done on process exit, and any later `sm` operations would just connect again
+pub fn finalize_sm_session() {
+    unsafe {
+        let _lock = sync::ScopedLock::new(G_SM_SESSION.get_lock());
+        if let Some(sm_session) = G_SM_SESSION.get().take() {
+            let _ = sm_session.get().detach_client(sf::ProcessId::new());
+        }
+    }
+}
+
 /// Wrapper for accessing a service and instantiating the wrapper interface over the specified service
 /// 
 /// For more information about this, check [`IService`]
 pub fn new_service_object<T: IService + 'static>() -> Result<mem::Shared<T>> {
-    let sm = new_named_port_object::<sm::UserInterface>()?;
-    let session_handle = sm.get().get_service_handle(T::get_name())?;
-    sm.get().detach_client(sf::ProcessId::new())?;
+    let session_handle = with_sm_session(|sm| sm.get_service_handle(T::get_name()))?;
     let mut object = T::new(sf::Session::from_handle(session_handle.handle));
     if T::as_domain() {
         object.convert_to_domain()?;
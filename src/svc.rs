@@ -465,6 +465,32 @@ pub fn send_sync_request(handle: Handle) -> Result<()> {
     }
 }
 
+#[inline(always)]
+pub fn send_sync_request_with_user_buffer(buffer: *mut u8, size: Size, handle: Handle) -> Result<()> {
+    extern "C" {
+        fn __nx_svc_send_sync_request_with_user_buffer(buffer: *mut u8, size: Size, handle: Handle) -> ResultCode;
+    }
+    
+    unsafe {
+        let rc = __nx_svc_send_sync_request_with_user_buffer(buffer, size, handle);
+        pack(rc, ())
+    }
+}
+
+#[inline(always)]
+pub fn send_async_request_with_user_buffer(buffer: *mut u8, size: Size, handle: Handle) -> Result<Handle> {
+    extern "C" {
+        fn __nx_svc_send_async_request_with_user_buffer(out_event_handle: *mut Handle, buffer: *mut u8, size: Size, handle: Handle) -> ResultCode;
+    }
+    
+    unsafe {
+        let mut event_handle: Handle = 0;
+
+        let rc = __nx_svc_send_async_request_with_user_buffer(&mut event_handle, buffer, size, handle);
+        pack(rc, event_handle)
+    }
+}
+
 #[inline(always)]
 pub fn get_process_id(process_handle: Handle) -> Result<u64> {
     extern "C" {
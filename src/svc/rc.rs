@@ -3,13 +3,20 @@
 pub const RESULT_MODULE: u32 = 1;
 
 result_define_group!(RESULT_MODULE => {
+    OutOfSessions: 7,
     InvalidSize: 101,
     InvalidAddress: 102,
+    OutOfResource: 103,
+    OutOfHandles: 105,
     InvalidCurrentMemory: 106,
     InvalidHandle: 114,
     TimedOut: 117,
     Cancelled: 118,
+    NotFound: 121,
+    Busy: 122,
     SessionClosed: 123,
     NotHandled: 124,
-    Debug: 128
+    InvalidState: 125,
+    Debug: 128,
+    LimitReached: 132
 });
\ No newline at end of file
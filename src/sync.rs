@@ -3,8 +3,10 @@
 use crate::diag::abort;
 use crate::svc;
 use crate::thread;
+use crate::mem;
 use core::cell::UnsafeCell;
 use core::arch::asm;
+use core::ops;
 
 const HANDLE_WAIT_MASK: u32 = 0x40000000;
 
@@ -252,6 +254,22 @@ impl<T> Locked<T> {
         obj_ref
     }
 
+    /// Locks the inner [`Mutex`], returning a guard giving access to the value until it's dropped
+    /// 
+    /// Unlike [`get`][`Locked::get`], the value is only accessed while the lock is held
+    pub fn lock(&self) -> LockedGuard<'_, T> {
+        self.get_lock().lock();
+        LockedGuard { locked: self }
+    }
+
+    /// Attempts to lock the inner [`Mutex`], returning a guard (like [`lock`][`Locked::lock`]) if it was successful
+    pub fn try_lock(&self) -> Option<LockedGuard<'_, T>> {
+        match self.get_lock().try_lock() {
+            true => Some(LockedGuard { locked: self }),
+            false => None
+        }
+    }
+
     /// Sets a value, doing a lock-unlock operation in the process
     pub fn set(&mut self, t: T) {
         self.get_lock().lock();
@@ -270,4 +288,73 @@ impl<T: Copy> Locked<T> {
         self.get_lock().unlock();
         obj_copy
     }
+}
+
+/// Represents a locked [`Locked`] value, which gets unlocked when this guard is dropped
+pub struct LockedGuard<'a, T> {
+    locked: &'a Locked<T>
+}
+
+impl<'a, T> ops::Deref for LockedGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {
+            &*self.locked.object_cell.get()
+        }
+    }
+}
+
+impl<'a, T> ops::DerefMut for LockedGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *self.locked.object_cell.get()
+        }
+    }
+}
+
+impl<'a, T> Drop for LockedGuard<'a, T> {
+    /// Unlocks the value as the [`LockedGuard`] is destroyed (likely out of scope)
+    fn drop(&mut self) {
+        self.locked.get_lock().unlock();
+    }
+}
+
+/// Represents a [`Shared`][`mem::Shared`] value only accessible while holding its lock, for values actually mutated from several threads
+/// 
+/// Plain [`Shared`][`mem::Shared`] values are meant for single-threaded usage (like objects of a single-threaded server, which shouldn't pay any locking cost), since [`get`][`mem::Shared::get`] hands out unguarded mutable references
+/// 
+/// Note that only the value access is synchronized: the reference count isn't atomic, thus instances must not be cloned/dropped concurrently from different threads (for instance, clone them before creating the threads and drop them after joining them)
+pub struct SharedMutex<T> {
+    shared: mem::Shared<Locked<T>>
+}
+
+impl<T> SharedMutex<T> {
+    /// Creates a new [`SharedMutex`] with a value
+    /// 
+    /// # Arguments
+    /// 
+    /// * `t`: The value to store
+    pub fn new(t: T) -> Self {
+        Self { shared: mem::Shared::new(Locked::new(false, t)) }
+    }
+
+    /// Locks the value, returning a guard giving access to it until it's dropped
+    #[inline]
+    pub fn lock(&self) -> LockedGuard<'_, T> {
+        self.shared.get().lock()
+    }
+
+    /// Attempts to lock the value, returning a guard (like [`lock`][`SharedMutex::lock`]) if it was successful
+    #[inline]
+    pub fn try_lock(&self) -> Option<LockedGuard<'_, T>> {
+        self.shared.get().try_lock()
+    }
+}
+
+impl<T> Clone for SharedMutex<T> {
+    /// Creates a new [`SharedMutex`] instance pointing to the same value
+    fn clone(&self) -> Self {
+        Self { shared: self.shared.clone() }
+    }
 }
\ No newline at end of file
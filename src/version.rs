@@ -33,6 +33,23 @@ impl Version {
     pub const fn new(major: u8, minor: u8, micro: u8) -> Self {
         Self { major, minor, micro }
     }
+
+    /// Returns whether this [`Version`] is lower or equal to `other` (usable in const contexts, unlike [`Ord`])
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other`: The [`Version`] to compare with
+    pub const fn is_lower_or_equal(&self, other: &Self) -> bool {
+        if self.major != other.major {
+            self.major < other.major
+        }
+        else if self.minor != other.minor {
+            self.minor < other.minor
+        }
+        else {
+            self.micro <= other.micro
+        }
+    }
 }
 
 impl Ord for Version {
@@ -134,6 +151,34 @@ impl VersionInterval {
         }
     }
 
+    /// Returns whether the interval is well-formed, this is, whether its minimum (if any) is lower or equal to its maximum (if any)
+    pub const fn is_valid(&self) -> bool {
+        match (&self.min, &self.max) {
+            (Some(min_v), Some(max_v)) => min_v.is_lower_or_equal(max_v),
+            _ => true
+        }
+    }
+
+    /// Returns whether the interval has any version in common with `other`
+    /// 
+    /// # Arguments
+    /// 
+    /// * `other`: The [`VersionInterval`] to check
+    pub const fn overlaps(&self, other: &Self) -> bool {
+        if let (Some(self_max_v), Some(other_min_v)) = (&self.max, &other.min) {
+            if !other_min_v.is_lower_or_equal(self_max_v) {
+                return false;
+            }
+        }
+        if let (Some(other_max_v), Some(self_min_v)) = (&other.max, &self.min) {
+            if !self_min_v.is_lower_or_equal(other_max_v) {
+                return false;
+            }
+        }
+
+        true
+    }
+
     /// Returns whether `ver` is contained in the interval
     /// 
     /// # Arguments
@@ -3,6 +3,7 @@
 use crate::result::*;
 use crate::svc;
 use crate::arm;
+use alloc::vec::Vec;
 
 /// Represents an event via a remote handle
 pub struct RemoteEvent {
@@ -190,4 +191,48 @@ pub fn wait(waiters: &[Waiter], timeout: i64) -> Result<usize> {
 #[inline]
 pub fn wait_handles(handles: &[svc::Handle], timeout: i64) -> Result<usize> {
     wait_impl(handles, timeout, handles_wait_fn)
+}
+
+/// Waits for several handles for a specified timeout, returning the index of the handle which signals first
+///
+/// This is the same as [`wait_handles`], named in contrast to [`poll_signaled`]
+///
+/// # Arguments
+///
+/// * `handles` - Handles to wait for
+/// * `timeout` - Wait timeout in nanoseconds, `-1` can be used to wait indefinitely
+#[inline]
+pub fn wait_any(handles: &[svc::Handle], timeout: i64) -> Result<usize> {
+    wait_handles(handles, timeout)
+}
+
+/// Gets the indices of all the handles which are currently signaled, without blocking
+///
+/// Since the kernel only reports one signaled handle per wait, this polls (zero timeout) repeatedly, leaving out the handles already reported
+///
+/// # Arguments
+///
+/// * `handles` - Handles to check
+pub fn poll_signaled(handles: &[svc::Handle]) -> Result<Vec<usize>> {
+    let mut signaled_indices: Vec<usize> = Vec::new();
+    let mut remaining_handles: Vec<svc::Handle> = handles.to_vec();
+    let mut remaining_indices: Vec<usize> = (0..handles.len()).collect();
+
+    while !remaining_handles.is_empty() {
+        match svc::wait_synchronization(remaining_handles.as_ptr(), remaining_handles.len() as u32, 0) {
+            Ok(index) => {
+                let index = index as usize;
+                remaining_handles.remove(index);
+                signaled_indices.push(remaining_indices.remove(index));
+            },
+            Err(rc) => {
+                if svc::rc::ResultTimedOut::matches(rc) {
+                    break;
+                }
+                return Err(rc);
+            }
+        };
+    }
+
+    Ok(signaled_indices)
 }
\ No newline at end of file